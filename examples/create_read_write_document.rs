@@ -48,7 +48,10 @@ async fn write_partial_document(session: &mut ServiceSession, doc_id: &str) -> e
         "tests",
         Some(doc_id),
         &obj,
-        documents::WriteOptions { merge: true },
+        documents::WriteOptions {
+            merge: true,
+            ..Default::default()
+        },
     )
     .await
 }
@@ -15,7 +15,7 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use super::jwt::{create_jwt_encoded, download_google_jwks, verify_access_token, JWKSet, JWT_AUDIENCE_IDENTITY};
-use crate::{errors::FirebaseError, jwt::TokenValidationResult};
+use crate::{emulator::Emulator, errors::FirebaseError, jwt::TokenValidationResult};
 
 type Error = super::errors::FirebaseError;
 
@@ -61,6 +61,9 @@ pub struct Credentials {
     /// be optimized for reading, hence the RwLock.
     #[serde(default, skip)]
     pub(crate) keys: Arc<RwLock<Keys>>,
+    /// The local emulators to use instead of the Google APIs, see [`Credentials::with_emulator`]
+    #[serde(default, skip)]
+    pub emulator: Option<Emulator>,
 }
 
 /// Converts a PEM (ascii base64) encoded private key into the binary der representation
@@ -107,6 +110,9 @@ Amtz4dJQ1YlGi0/BGhK2lg==
 impl Credentials {
     /// Create a [`Credentials`] object by parsing a google-service-account json string
     ///
+    /// The local emulators given by the `FIRESTORE_EMULATOR_HOST` and `FIREBASE_AUTH_EMULATOR_HOST`
+    /// environment variables are used, if set. See [`Credentials::with_emulator`].
+    ///
     /// Example:
     ///
     /// Assuming that your firebase service account credentials file is called "service-account-test.json" and
@@ -128,6 +134,7 @@ impl Credentials {
     /// * https://www.googleapis.com/service_accounts/v1/jwk/{your-service-account-email}
     pub async fn new(credentials_file_content: &str) -> Result<Credentials, Error> {
         let mut credentials: Credentials = serde_json::from_str(credentials_file_content)?;
+        credentials.emulator = Emulator::from_env();
         credentials.compute_secret().await?;
         Ok(credentials)
     }
@@ -139,10 +146,45 @@ impl Credentials {
     pub async fn from_file(credential_file: &str) -> Result<Self, Error> {
         let f = BufReader::new(File::open(credential_file)?);
         let mut credentials: Credentials = serde_json::from_reader(f)?;
+        credentials.emulator = Emulator::from_env();
         credentials.compute_secret().await?;
         Ok(credentials)
     }
 
+    /// Use the given local Firestore and Firebase Auth emulators instead of the Google APIs.
+    ///
+    /// Sessions created with these credentials talk to the emulators. Service account sessions use the
+    /// emulator's `owner` bearer token, custom tokens are not signed and access tokens are not verified.
+    ///
+    /// A private key is not required, see [`Credentials::for_emulator`].
+    pub fn with_emulator(mut self, emulator: Emulator) -> Credentials {
+        self.emulator = Some(emulator);
+        self
+    }
+
+    /// Create [`Credentials`] without a service account, with just a project id, for the given local emulators.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use firestore_db_and_auth::{Credentials, emulator::Emulator};
+    ///
+    /// let c = Credentials::for_emulator(
+    ///     "demo-project",
+    ///     Emulator {
+    ///         firestore_host: Some("localhost:8080".to_owned()),
+    ///         auth_host: Some("localhost:9099".to_owned()),
+    ///     },
+    /// );
+    /// ```
+    pub fn for_emulator(project_id: &str, emulator: Emulator) -> Credentials {
+        Credentials {
+            project_id: project_id.to_owned(),
+            ..Default::default()
+        }
+        .with_emulator(emulator)
+    }
+
     /// Adds public-key JWKs to a credentials instance and returns it.
     ///
     /// This method will also verify that the given JWKs files allow verification of Google access tokens.
@@ -172,15 +214,23 @@ impl Credentials {
     ///     .download_jwkset().await.unwrap();
     /// # })
     /// ```
+    ///
+    /// Nothing is downloaded or verified if an auth emulator is used, see [`Credentials::with_emulator`].
     pub async fn download_jwkset(self) -> Result<Credentials, Error> {
+        if self.auth_emulator() {
+            return Ok(self);
+        }
         self.download_google_jwks().await?;
         self.verify().await?;
         Ok(self)
     }
 
     /// Verifies that creating access tokens is possible with the given credentials and public keys.
-    /// Returns an empty result type on success.
+    /// Returns an empty result type on success. Always succeeds if an auth emulator is used.
     pub async fn verify(&self) -> Result<(), Error> {
+        if self.auth_emulator() {
+            return Ok(());
+        }
         let access_token = create_jwt_encoded(
             &self,
             Some(["admin"].iter()),
@@ -198,6 +248,20 @@ impl Credentials {
         verify_access_token(&self, token).await
     }
 
+    /// True if tokens are issued by the Firebase Auth emulator, which neither signs nor verifies tokens
+    pub(crate) fn auth_emulator(&self) -> bool {
+        self.emulator
+            .as_ref()
+            .map_or(false, |emulator| emulator.auth_host.is_some())
+    }
+
+    /// True if the Firestore emulator is used, which accepts the `owner` bearer token
+    pub(crate) fn firestore_emulator(&self) -> bool {
+        self.emulator
+            .as_ref()
+            .map_or(false, |emulator| emulator.firestore_host.is_some())
+    }
+
     /// Find the secret in the jwt set that matches the given key id, if any.
     /// Used for jws validation
     pub async fn decode_secret(&self, kid: &str) -> Result<Option<Arc<biscuit::jws::Secret>>, Error> {
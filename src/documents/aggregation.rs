@@ -0,0 +1,159 @@
+use super::*;
+
+/// An aggregation of the documents that match a query, see [`aggregate`].
+///
+/// Each aggregation has an alias. The aggregation result is available under this name.
+#[derive(Clone, Debug)]
+pub struct Aggregation(dto::Aggregation);
+
+impl Aggregation {
+    /// Count the matching documents
+    pub fn count(alias: impl Into<String>) -> Self {
+        Aggregation(dto::Aggregation {
+            alias: Some(alias.into()),
+            count: Some(dto::Count::default()),
+            ..Default::default()
+        })
+    }
+
+    /// Count the matching documents, but stop counting at the given number.
+    /// Use this to limit the number of billed index entries for large collections.
+    pub fn count_up_to(alias: impl Into<String>, up_to: i64) -> Self {
+        Aggregation(dto::Aggregation {
+            alias: Some(alias.into()),
+            count: Some(dto::Count {
+                up_to: Some(up_to.to_string()),
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// Sum the numeric values of the given field. Non-numeric values are ignored.
+    pub fn sum(alias: impl Into<String>, field: impl Into<String>) -> Self {
+        Aggregation(dto::Aggregation {
+            alias: Some(alias.into()),
+            sum: Some(dto::Sum {
+                field: Some(dto::FieldReference {
+                    field_path: field.into(),
+                }),
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// Average the numeric values of the given field. Non-numeric values are ignored.
+    /// The result is null if there are no numeric values.
+    pub fn avg(alias: impl Into<String>, field: impl Into<String>) -> Self {
+        Aggregation(dto::Aggregation {
+            alias: Some(alias.into()),
+            avg: Some(dto::Avg {
+                field: Some(dto::FieldReference {
+                    field_path: field.into(),
+                }),
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+///
+/// Aggregates the documents matching the given query on the server side,
+/// without transferring the documents. The aggregation results are deserialized into the given type,
+/// with the aggregation aliases as field names.
+///
+/// Example:
+/// ```no_run
+/// use serde::Deserialize;
+/// #[derive(Debug, Deserialize)]
+/// struct Stats { total: i64, sum_int: i64, avg_int: Option<f64>, }
+///
+/// use firestore_db_and_auth::{documents, documents::{Aggregation, QueryBuilder}};
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// let query = QueryBuilder::new("tests");
+/// let aggregations = [
+///     Aggregation::count("total"),
+///     Aggregation::sum("sum_int", "an_int"),
+///     Aggregation::avg("avg_int", "an_int"),
+/// ];
+/// let stats: Stats = documents::aggregate(&session, &query, &aggregations).await.unwrap();
+/// println!("{:?}", stats);
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'query' The query
+/// * 'aggregations' The aggregations. Firestore allows up to five aggregations per request.
+pub async fn aggregate<T>(
+    auth: &impl FirebaseAuthBearer,
+    query: &QueryBuilder,
+    aggregations: &[Aggregation],
+) -> Result<T>
+where
+    for<'b> T: Deserialize<'b>,
+{
+    let url = firebase_url_parent_action(auth, query.parent_path(), "runAggregationQuery");
+    let request = aggregation_request(query, aggregations);
+
+    let resp = auth
+        .client()
+        .post(&url)
+        .bearer_auth(auth.access_token().await)
+        .json(&request)
+        .send()
+        .await?;
+
+    let resp = extract_google_api_error_async(resp, || query.collection_id().to_owned()).await?;
+
+    let responses: Vec<dto::RunAggregationQueryResponse> = resp.json().await?;
+    let result = responses
+        .into_iter()
+        .find_map(|response| response.result)
+        .ok_or(FirebaseError::Generic("No result in the runAggregationQuery response"))?;
+
+    document_to_pod(
+        &dto::Document {
+            fields: result.aggregate_fields,
+            ..Default::default()
+        },
+        None,
+    )
+}
+
+fn aggregation_request(query: &QueryBuilder, aggregations: &[Aggregation]) -> dto::RunAggregationQueryRequest {
+    dto::RunAggregationQueryRequest {
+        structured_aggregation_query: Some(dto::StructuredAggregationQuery {
+            structured_query: Some(query.structured_query()),
+            aggregations: Some(aggregations.iter().map(|a| a.0.clone()).collect()),
+        }),
+        read_time: query.read_time_param(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn aggregation_request_json() {
+    let query = QueryBuilder::new("tests").limit(100);
+    let request = aggregation_request(
+        &query,
+        &[
+            Aggregation::count_up_to("total", 50),
+            Aggregation::sum("sum_int", "an_int"),
+            Aggregation::avg("avg_int", "an_int"),
+        ],
+    );
+    let json = serde_json::to_value(request).unwrap();
+    assert_eq!(json["structuredAggregationQuery"]["structuredQuery"]["limit"], 100);
+    assert_eq!(
+        json["structuredAggregationQuery"]["aggregations"],
+        serde_json::json!([
+            {"alias": "total", "count": {"upTo": "50"}},
+            {"alias": "sum_int", "sum": {"field": {"fieldPath": "an_int"}}},
+            {"alias": "avg_int", "avg": {"field": {"fieldPath": "an_int"}}}
+        ])
+    );
+}
@@ -0,0 +1,196 @@
+use super::*;
+use chrono::{DateTime, Utc};
+
+/// The maximum number of writes that Firestore accepts within a single commit
+pub const MAX_BATCH_WRITES: usize = 500;
+
+/// This is returned by [`WriteBatch::commit`] in a successful case.
+#[derive(Debug, Clone)]
+pub struct CommitResult {
+    /// The result of each write, in the order the writes have been added to the batch
+    pub write_results: Vec<dto::WriteResult>,
+    /// The time at which the commit occurred
+    pub commit_time: Option<DateTime<Utc>>,
+}
+
+///
+/// A batch of writes that are committed atomically. Either all writes succeed or none of them is applied.
+///
+/// Writes are only collected until [`WriteBatch::commit`] is called. A batch can contain
+/// at most [`MAX_BATCH_WRITES`] writes.
+///
+/// Example:
+/// ```no_run
+/// use serde::{Serialize, Deserialize};
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct DemoDTO { a_string: String, an_int: u32, }
+///
+/// use firestore_db_and_auth::documents;
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// let mut batch = documents::WriteBatch::new(&session);
+/// let obj = DemoDTO { a_string: "abc".to_owned(), an_int: 12 };
+/// batch.set("tests", "batch_a", &obj, documents::WriteOptions::default())?;
+/// batch.set("tests", "batch_b", &obj, documents::WriteOptions::default())?;
+/// batch.delete("tests/batch_c", false)?;
+/// let result = batch.commit().await?;
+/// println!("Committed {} writes at {:?}", result.write_results.len(), result.commit_time);
+/// # Ok::<(), firestore_db_and_auth::errors::FirebaseError>(())
+/// # });
+/// ```
+pub struct WriteBatch<'a, AUTH> {
+    auth: &'a AUTH,
+    writes: Vec<dto::Write>,
+}
+
+impl<'a, AUTH: FirebaseAuthBearer> WriteBatch<'a, AUTH> {
+    /// Creates a new, empty batch
+    pub fn new(auth: &'a AUTH) -> Self {
+        WriteBatch {
+            auth,
+            writes: Vec::new(),
+        }
+    }
+
+    /// The number of writes in this batch
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    /// Returns true if this batch does not contain any writes
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Add a document write to the batch. See [`write`] for the options.
+    ///
+    /// ## Arguments
+    /// * 'path' The document path / collection; For example "my_collection" or "a/nested/collection"
+    /// * 'document_id' The document id. Make sure that you do not include the document id in the path argument.
+    /// * 'document' The document
+    /// * 'options' Write options
+    pub fn set<T>(
+        &mut self,
+        path: impl AsRef<str>,
+        document_id: &str,
+        document: &T,
+        options: WriteOptions,
+    ) -> Result<()>
+    where
+        T: Serialize,
+    {
+        let document_name = document_name(self.auth, path.as_ref(), document_id)?;
+        let write = document_write(document_name, document, &options)?;
+        self.push(write)
+    }
+
+    /// Add a write to the batch that only updates the fields of the given document.
+    /// The commit fails if the target document does not exist.
+    ///
+    /// This is the same as [`WriteBatch::set`] with [`WriteOptions::merge`] set.
+    pub fn update<T>(&mut self, path: impl AsRef<str>, document_id: &str, document: &T) -> Result<()>
+    where
+        T: Serialize,
+    {
+        self.set(
+            path,
+            document_id,
+            document,
+            WriteOptions {
+                merge: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Add a document deletion to the batch.
+    ///
+    /// ## Arguments
+    /// * 'path' The relative collection path and document id, for example "my_collection/document_id"
+    /// * 'fail_if_not_existing' If true the commit will fail if the document does not exist.
+    pub fn delete(&mut self, path: impl AsRef<str>, fail_if_not_existing: bool) -> Result<()> {
+        self.push(dto::Write {
+            delete: Some(DocumentRef::new(path)?.name(self.auth)),
+            current_document: match fail_if_not_existing {
+                true => Some(dto::Precondition {
+                    exists: Some(true),
+                    ..Default::default()
+                }),
+                false => None,
+            },
+            ..Default::default()
+        })
+    }
+
+    /// Add a raw write operation to the batch. Use this for custom preconditions
+    /// via [`dto::Write::current_document`].
+    ///
+    /// Returns an error if the batch already contains [`MAX_BATCH_WRITES`] writes.
+    pub fn push(&mut self, write: dto::Write) -> Result<()> {
+        if self.writes.len() >= MAX_BATCH_WRITES {
+            return Err(FirebaseError::Generic(
+                "A write batch cannot contain more than 500 writes",
+            ));
+        }
+        self.writes.push(write);
+        Ok(())
+    }
+
+    /// Commits all writes of this batch atomically.
+    pub async fn commit(self) -> Result<CommitResult> {
+        let response = commit(self.auth, self.writes, None).await?;
+        Ok(CommitResult {
+            write_results: response.write_results.unwrap_or_default(),
+            commit_time: parse_timestamp(
+                &response.commit_time,
+                "Failed to parse rfc3339 date from 'commit_time' field",
+            )?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::tests::TestAuth;
+
+    #[test]
+    fn write_batch_limit() {
+        let auth = TestAuth::new();
+        let mut batch = WriteBatch::new(&auth);
+        for i in 0..MAX_BATCH_WRITES {
+            batch.delete(&format!("tests/{}", i), false).unwrap();
+        }
+        assert_eq!(batch.len(), MAX_BATCH_WRITES);
+        assert!(batch.delete("tests/too_many", false).is_err());
+        assert_eq!(batch.len(), MAX_BATCH_WRITES);
+    }
+
+    #[test]
+    fn write_batch_operations() {
+        #[derive(Serialize)]
+        struct Partial {
+            an_int: u32,
+        }
+
+        let auth = TestAuth::new();
+        let mut batch = WriteBatch::new(&auth);
+        batch.update("tests", "a", &Partial { an_int: 1 }).unwrap();
+        batch.delete("tests/b", true).unwrap();
+
+        let json = serde_json::to_value(&batch.writes).unwrap();
+        assert_eq!(
+            json[0]["update"]["name"],
+            "projects/project/databases/(default)/documents/tests/a"
+        );
+        assert_eq!(json[0]["currentDocument"]["exists"], true);
+        assert_eq!(
+            json[1]["delete"],
+            "projects/project/databases/(default)/documents/tests/b"
+        );
+        assert_eq!(json[1]["currentDocument"]["exists"], true);
+    }
+}
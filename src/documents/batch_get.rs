@@ -0,0 +1,316 @@
+use super::json_array_stream::JsonArrayParser;
+use super::*;
+use futures::stream::{self, BoxStream};
+use std::collections::{HashMap, VecDeque};
+
+/// The number of documents that are requested with a single batchGet request.
+/// Larger lists of documents are split into multiple requests.
+pub const BATCH_GET_CHUNK_SIZE: usize = 500;
+
+///
+/// Reads many documents with as few requests as possible and returns a stream of
+/// deserialized documents and their metadata, in the order of the given paths.
+///
+/// Documents that do not exist are returned as `None`. Lists with more than [`BATCH_GET_CHUNK_SIZE`]
+/// paths are split into multiple requests. If a path is not a valid document path, the stream only returns an error.
+/// The responses are parsed while they arrive. Only documents that arrive ahead of documents that were
+/// requested earlier are held back, to return all documents in the requested order.
+///
+/// Example:
+/// ```no_run
+/// # use futures::StreamExt;
+/// use serde::{Serialize, Deserialize};
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct DemoDTO { a_string: String, an_int: u32, }
+///
+/// use firestore_db_and_auth::documents;
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// let paths = ["tests/a", "tests/b", "tests/does_not_exist"];
+/// let mut stream = documents::batch_get(&session, &paths, documents::ReadOptions::default());
+/// while let Some(result) = stream.next().await {
+///     match result.unwrap() {
+///         Some((doc, metadata)) => {
+///             let doc: DemoDTO = doc;
+///             println!("{}: {:?}", metadata.name, doc);
+///         }
+///         None => println!("Missing document"),
+///     }
+/// }
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'paths' The relative collection paths and document ids, for example "my_collection/document_id"
+/// * 'options' Read options like a field mask or a read time
+pub fn batch_get<T, AUTH>(
+    auth: &AUTH,
+    paths: &[impl AsRef<str>],
+    options: ReadOptions,
+) -> BoxStream<'static, Result<Option<(T, DocumentMeta)>>>
+where
+    for<'b> T: Deserialize<'b> + Send + 'static,
+    AUTH: FirebaseAuthBearer + Clone + Send + Sync + 'static,
+{
+    let document_names: Result<Vec<String>> = paths
+        .iter()
+        .map(|path| Ok(DocumentRef::new(path)?.name(auth)))
+        .collect();
+    let document_names = match document_names {
+        Ok(document_names) => document_names,
+        Err(e) => return error_stream(e),
+    };
+    let chunks: VecDeque<Vec<String>> = document_names
+        .chunks(BATCH_GET_CHUNK_SIZE)
+        .map(|chunk| chunk.to_vec())
+        .collect();
+
+    let state = BatchGetState {
+        auth: auth.clone(),
+        options,
+        chunks,
+        current: None,
+    };
+
+    Box::pin(stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        loop {
+            let (response, results) = match state.current.as_mut() {
+                Some((response, results)) if !results.is_done() => (response, results),
+                _ => {
+                    let document_names = state.chunks.pop_front()?;
+                    match send_batch_get_request(&state.auth, &document_names, &state.options).await {
+                        Ok(response) => state.current = Some((response, ChunkResults::new(document_names))),
+                        Err(e) => return Some((Err(e), None)),
+                    }
+                    continue;
+                }
+            };
+            match results.next_result() {
+                Ok(Some(result)) => return Some((result, Some(state))),
+                Ok(None) => match response.chunk().await {
+                    Ok(Some(chunk)) => results.push(&chunk),
+                    Ok(None) => results.end(),
+                    Err(e) => return Some((Err(e.into()), None)),
+                },
+                Err(e) => return Some((Err(e), None)),
+            }
+        }
+    }))
+}
+
+/// The result for a single requested document
+type BatchGetResult<T> = Result<Option<(T, DocumentMeta)>>;
+
+struct BatchGetState<T, AUTH> {
+    auth: AUTH,
+    options: ReadOptions,
+    chunks: VecDeque<Vec<String>>,
+    /// The response of the current chunk and its results
+    current: Option<(reqwest::Response, ChunkResults<T>)>,
+}
+
+/// The results of a single batchGet request. The response is parsed while it arrives
+/// and the results are returned in the requested order.
+struct ChunkResults<T> {
+    parser: JsonArrayParser,
+    /// The requested document names whose results have not been returned yet, in the requested order
+    document_names: VecDeque<String>,
+    /// Results that arrived ahead of the results of documents that were requested earlier
+    received: HashMap<String, BatchGetResult<T>>,
+    /// The whole response has been received
+    ended: bool,
+}
+
+impl<T> ChunkResults<T>
+where
+    for<'b> T: Deserialize<'b>,
+{
+    fn new(document_names: Vec<String>) -> Self {
+        ChunkResults {
+            parser: JsonArrayParser::default(),
+            document_names: document_names.into(),
+            received: HashMap::new(),
+            ended: false,
+        }
+    }
+
+    /// Append received data of the response
+    fn push(&mut self, data: &[u8]) {
+        self.parser.push(data);
+    }
+
+    /// Marks the response as completely received
+    fn end(&mut self) {
+        self.ended = true;
+    }
+
+    /// Returns true if the results of all requested documents have been returned
+    fn is_done(&self) -> bool {
+        self.document_names.is_empty()
+    }
+
+    /// Returns the result of the next requested document, or None if more data is required.
+    /// Once the response has ended, documents without a response are returned as errors.
+    /// An error is returned for invalid or incomplete responses.
+    fn next_result(&mut self) -> Result<Option<BatchGetResult<T>>> {
+        loop {
+            let document_name = match self.document_names.front() {
+                Some(document_name) => document_name,
+                None => return Ok(None),
+            };
+            if let Some(result) = self.received.remove(document_name) {
+                self.document_names.pop_front();
+                return Ok(Some(result));
+            }
+
+            // The responses are not necessarily in the requested order
+            let response = match self.parser.next_element::<dto::BatchGetDocumentsResponse>() {
+                Some(response) => response?,
+                None if !self.ended => return Ok(None),
+                None if self.parser.has_pending_element() => {
+                    return Err(FirebaseError::Generic("Incomplete batchGet response"))
+                }
+                None => {
+                    self.document_names.pop_front();
+                    return Ok(Some(Err(FirebaseError::Generic(
+                        "No batchGet response for a requested document",
+                    ))));
+                }
+            };
+            let name = match (response.found.as_ref(), response.missing.as_ref()) {
+                (Some(document), _) => document.name.clone(),
+                (None, Some(missing)) => missing.clone(),
+                (None, None) => continue,
+            };
+            self.received.insert(name, batch_get_response_to_pod(&response));
+        }
+    }
+}
+
+/// Requests the given documents and returns the response from `reqwest`
+async fn send_batch_get_request(
+    auth: &impl FirebaseAuthBearer,
+    document_names: &[String],
+    options: &ReadOptions,
+) -> Result<reqwest::Response> {
+    let url = firebase_url_action(auth, "batchGet");
+    let request = dto::BatchGetDocumentsRequest {
+        documents: Some(document_names.to_vec()),
+        mask: options.document_mask(),
+        read_time: options.read_time.as_ref().map(format_timestamp),
+        ..Default::default()
+    };
+
+    let resp = auth
+        .client()
+        .post(&url)
+        .bearer_auth(auth.access_token().await)
+        .json(&request)
+        .send()
+        .await?;
+
+    extract_google_api_error_async(resp, || "batchGet".to_owned()).await
+}
+
+fn batch_get_response_to_pod<T>(response: &dto::BatchGetDocumentsResponse) -> Result<Option<(T, DocumentMeta)>>
+where
+    for<'b> T: Deserialize<'b>,
+{
+    let document = match response.found.as_ref() {
+        Some(document) => document,
+        None => return Ok(None),
+    };
+    let mut meta = DocumentMeta::from_document(document)?;
+    meta.read_time = parse_timestamp(
+        &response.read_time,
+        "Failed to parse rfc3339 date from 'read_time' field",
+    )?;
+    Ok(Some((document_to_pod(document, None)?, meta)))
+}
+
+#[test]
+fn batch_get_response_missing() {
+    let missing = dto::BatchGetDocumentsResponse {
+        missing: Some("projects/p/databases/(default)/documents/tests/a".to_owned()),
+        ..Default::default()
+    };
+    let result: Option<(serde_json::Value, DocumentMeta)> = batch_get_response_to_pod(&missing).unwrap();
+    assert!(result.is_none());
+
+    let found = dto::BatchGetDocumentsResponse {
+        found: Some(dto::Document {
+            name: "projects/p/databases/(default)/documents/tests/b".to_owned(),
+            ..Default::default()
+        }),
+        read_time: Some("2020-04-28T14:52:51.250511Z".to_owned()),
+        ..Default::default()
+    };
+    let (_, meta): (serde_json::Value, _) = batch_get_response_to_pod(&found).unwrap().unwrap();
+    assert_eq!(meta.name, "projects/p/databases/(default)/documents/tests/b");
+    assert!(meta.read_time.is_some());
+}
+
+#[test]
+fn batch_get_invalid_path() {
+    use futures::StreamExt;
+    let auth = tests::TestAuth::new();
+    let mut stream = batch_get::<serde_json::Value, _>(&auth, &["tests/a", "tests"], ReadOptions::default());
+    let result = futures::executor::block_on(stream.next()).unwrap();
+    assert!(matches!(result, Err(FirebaseError::Generic(_))));
+    assert!(futures::executor::block_on(stream.next()).is_none());
+}
+
+#[test]
+fn batch_get_chunk_results_in_requested_order() {
+    let name = |id: &str| format!("projects/p/databases/(default)/documents/tests/{}", id);
+    let input = format!(
+        r#"[{{"found": {{"name": "{}", "fields": {{"n": {{"integerValue": "3"}}}}}}}},
+        {{"missing": "{}"}},
+        {{"found": {{"name": "{}", "fields": {{"n": {{"integerValue": "2"}}}}}}}}]"#,
+        name("c"),
+        name("a"),
+        name("b")
+    );
+    let mut results = ChunkResults::<serde_json::Value>::new(vec![name("a"), name("b"), name("c"), name("d")]);
+
+    // Feed the response in small pieces, like it arrives
+    let mut returned = Vec::new();
+    for chunk in input.as_bytes().chunks(7) {
+        results.push(chunk);
+        while let Some(result) = results.next_result().unwrap() {
+            returned.push(result.unwrap().map(|(doc, meta)| (doc["n"].clone(), meta.id)));
+        }
+    }
+    assert_eq!(
+        returned,
+        vec![
+            None,
+            Some((serde_json::json!(2), "b".to_owned())),
+            Some((serde_json::json!(3), "c".to_owned()))
+        ]
+    );
+    assert!(!results.is_done());
+
+    // A requested document without a response
+    results.end();
+    assert!(matches!(
+        results.next_result(),
+        Ok(Some(Err(FirebaseError::Generic(_))))
+    ));
+    assert!(results.is_done());
+}
+
+#[test]
+fn batch_get_chunk_results_incomplete() {
+    let mut results =
+        ChunkResults::<serde_json::Value>::new(vec!["projects/p/databases/(default)/documents/tests/a".to_owned()]);
+    results.push(br#"[{"missing": "projects/p/data"#);
+    assert!(matches!(results.next_result(), Ok(None)));
+    results.end();
+    assert!(results.next_result().is_err());
+}
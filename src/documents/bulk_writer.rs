@@ -0,0 +1,509 @@
+use super::*;
+use futures::channel::oneshot;
+use futures::stream::{FuturesUnordered, StreamExt};
+use futures::FutureExt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// The gRPC status codes of failed writes that are retried: DEADLINE_EXCEEDED, RESOURCE_EXHAUSTED,
+/// ABORTED, INTERNAL and UNAVAILABLE
+const RETRYABLE_CODES: [i32; 5] = [4, 8, 10, 13, 14];
+
+/// Options for a [`BulkWriter`]
+#[derive(Clone, Debug)]
+pub struct BulkWriterOptions {
+    /// The number of writes that are sent with a single batchWrite request.
+    /// Defaults to 20, at most [`MAX_BATCH_WRITES`].
+    pub batch_size: usize,
+    /// The number of writes per second at the start. Defaults to 500.
+    /// The rate grows by 50% every 5 minutes, following Google's 500/50/5 ramp-up rule.
+    pub initial_ops_per_second: u32,
+    /// The maximum number of writes per second. Defaults to 10000.
+    pub max_ops_per_second: u32,
+    /// The maximum number of attempts for a single write. Defaults to 10.
+    pub max_attempts: u32,
+    /// The maximum number of batches that are sent at the same time. Defaults to 50.
+    /// Queueing a write waits while this many batches are in flight.
+    pub max_in_flight_batches: usize,
+}
+
+impl Default for BulkWriterOptions {
+    fn default() -> Self {
+        BulkWriterOptions {
+            batch_size: 20,
+            initial_ops_per_second: 500,
+            max_ops_per_second: 10_000,
+            max_attempts: 10,
+            max_in_flight_batches: 50,
+        }
+    }
+}
+
+///
+/// Queues a large number of writes and sends them in parallel with non-atomic batchWrite requests.
+///
+/// In contrast to a [`WriteBatch`], every write succeeds or fails on its own. Each queued write
+/// returns a [`BulkWriteHandle`], a future that resolves to the result of that write.
+/// Writes that fail with a transient error, like a contention or an overloaded server, are retried with a backoff.
+///
+/// The writer starts with 500 writes per second and increases the rate by 50% every 5 minutes,
+/// as recommended by Google for ramping up traffic to new collections.
+///
+/// Batches are sent in the background as soon as they are full, which requires a tokio runtime.
+/// At most [`BulkWriterOptions::max_in_flight_batches`] batches are in flight at the same time.
+/// Queueing a write waits for a free slot when a batch is full, which keeps the memory usage bounded.
+/// Call [`BulkWriter::flush`] to send the remaining writes and wait for all writes to complete.
+/// Writes that have not been sent when the writer is dropped fail.
+///
+/// Example:
+/// ```no_run
+/// use serde::Serialize;
+/// #[derive(Serialize)]
+/// struct DemoDTO { an_int: u32, }
+///
+/// use firestore_db_and_auth::documents;
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// let mut writer = documents::BulkWriter::new(&session);
+/// let mut handles = Vec::new();
+/// for i in 0..10_000 {
+///     let handle = writer.set("tests", &format!("bulk_{}", i), &DemoDTO { an_int: i }, documents::WriteOptions::default()).await?;
+///     handles.push(handle);
+/// }
+/// writer.flush().await;
+/// for handle in handles {
+///     if let Err(e) = handle.await {
+///         println!("Write failed: {}", e);
+///     }
+/// }
+/// # Ok::<(), firestore_db_and_auth::errors::FirebaseError>(())
+/// # });
+/// ```
+pub struct BulkWriter<AUTH> {
+    auth: AUTH,
+    options: BulkWriterOptions,
+    pending: Vec<BulkWriteOperation>,
+    rate_limiter: Arc<Mutex<RateLimiter>>,
+    tasks: FuturesUnordered<tokio::task::JoinHandle<()>>,
+}
+
+impl<AUTH> BulkWriter<AUTH>
+where
+    AUTH: FirebaseAuthBearer + Clone + Send + Sync + 'static,
+{
+    /// Creates a bulk writer with the default options
+    pub fn new(auth: &AUTH) -> Self {
+        Self::with_options(auth, BulkWriterOptions::default())
+    }
+
+    /// Creates a bulk writer with the given options
+    pub fn with_options(auth: &AUTH, options: BulkWriterOptions) -> Self {
+        let rate_limiter = RateLimiter::new(
+            options.initial_ops_per_second,
+            options.max_ops_per_second,
+            Instant::now(),
+        );
+        BulkWriter {
+            auth: auth.clone(),
+            options,
+            pending: Vec::new(),
+            rate_limiter: Arc::new(Mutex::new(rate_limiter)),
+            tasks: FuturesUnordered::new(),
+        }
+    }
+
+    /// Queue a document write. See [`write`] for the options.
+    ///
+    /// ## Arguments
+    /// * 'path' The document path / collection; For example "my_collection" or "a/nested/collection"
+    /// * 'document_id' The document id. Make sure that you do not include the document id in the path argument.
+    /// * 'document' The document
+    /// * 'options' Write options
+    pub async fn set<T>(
+        &mut self,
+        path: impl AsRef<str>,
+        document_id: &str,
+        document: &T,
+        options: WriteOptions,
+    ) -> Result<BulkWriteHandle>
+    where
+        T: Serialize,
+    {
+        let document_name = document_name(&self.auth, path.as_ref(), document_id)?;
+        let write = document_write(document_name, document, &options)?;
+        Ok(self.push(write).await)
+    }
+
+    /// Queue a write that only updates the fields of the given document.
+    /// The write fails if the target document does not exist.
+    ///
+    /// This is the same as [`BulkWriter::set`] with [`WriteOptions::merge`] set.
+    pub async fn update<T>(&mut self, path: impl AsRef<str>, document_id: &str, document: &T) -> Result<BulkWriteHandle>
+    where
+        T: Serialize,
+    {
+        self.set(
+            path,
+            document_id,
+            document,
+            WriteOptions {
+                merge: true,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Queue a document deletion.
+    ///
+    /// ## Arguments
+    /// * 'path' The relative collection path and document id, for example "my_collection/document_id"
+    /// * 'fail_if_not_existing' If true the write will fail if the document does not exist.
+    pub async fn delete(&mut self, path: impl AsRef<str>, fail_if_not_existing: bool) -> Result<BulkWriteHandle> {
+        let write = dto::Write {
+            delete: Some(DocumentRef::new(path)?.name(&self.auth)),
+            current_document: precondition(fail_if_not_existing.then_some(true), None),
+            ..Default::default()
+        };
+        Ok(self.push(write).await)
+    }
+
+    /// Queue a raw write operation. Use this for custom preconditions via [`dto::Write::current_document`].
+    /// Waits for a free slot if the batch is full and the maximum number of batches is in flight.
+    pub async fn push(&mut self, write: dto::Write) -> BulkWriteHandle {
+        let (sender, receiver) = oneshot::channel();
+        self.pending.push(BulkWriteOperation { write, sender });
+        if self.pending.len() >= self.options.batch_size.clamp(1, MAX_BATCH_WRITES) {
+            self.send_pending().await;
+        }
+        BulkWriteHandle(receiver)
+    }
+
+    /// Sends all queued writes and waits until all writes have completed or finally failed.
+    /// The results are available via the [`BulkWriteHandle`]s of the writes.
+    pub async fn flush(&mut self) {
+        self.send_pending().await;
+        // A panicked task drops its result senders. The affected handles return an error.
+        while self.tasks.next().await.is_some() {}
+    }
+
+    /// The number of writes that are queued, but not yet sent
+    pub fn pending_writes(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// The number of batches that have been sent, but not yet completed
+    pub fn in_flight_batches(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Sends the queued writes as one batch in the background, after waiting for a free slot
+    async fn send_pending(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        // Drop the handles of completed batches
+        while let Some(Some(_)) = self.tasks.next().now_or_never() {}
+        while self.tasks.len() >= self.options.max_in_flight_batches.max(1) {
+            self.tasks.next().await;
+        }
+        let operations = std::mem::take(&mut self.pending);
+        let auth = self.auth.clone();
+        let rate_limiter = self.rate_limiter.clone();
+        let max_attempts = self.options.max_attempts.max(1);
+        self.tasks.push(tokio::spawn(async move {
+            send_batch(&auth, &rate_limiter, operations, max_attempts).await
+        }));
+    }
+}
+
+/// A queued write of a [`BulkWriter`] and the sender for its result
+struct BulkWriteOperation {
+    write: dto::Write,
+    sender: oneshot::Sender<Result<dto::WriteResult>>,
+}
+
+impl BulkWriteOperation {
+    fn complete(self, result: Result<dto::WriteResult>) {
+        // The handle may have been dropped, nobody is interested in the result then
+        let _ = self.sender.send(result);
+    }
+
+    /// The document name of this write, used as error context
+    fn context(&self) -> String {
+        match (&self.write.update, &self.write.delete) {
+            (Some(document), _) => document.name.clone(),
+            (None, Some(name)) => name.clone(),
+            (None, None) => "batchWrite".to_owned(),
+        }
+    }
+}
+
+/// The result of a single write of a [`BulkWriter`]. This is a future that resolves
+/// when the write has succeeded or finally failed.
+pub struct BulkWriteHandle(oneshot::Receiver<Result<dto::WriteResult>>);
+
+impl Future for BulkWriteHandle {
+    type Output = Result<dto::WriteResult>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx).map(|result| {
+            result.unwrap_or(Err(FirebaseError::Generic(
+                "The bulk writer has been dropped before the write has been sent",
+            )))
+        })
+    }
+}
+
+/// Sends the given writes with batchWrite requests until all writes have succeeded or finally failed
+async fn send_batch(
+    auth: &impl FirebaseAuthBearer,
+    rate_limiter: &Mutex<RateLimiter>,
+    mut operations: Vec<BulkWriteOperation>,
+    max_attempts: u32,
+) {
+    let mut attempt = 1;
+    while !operations.is_empty() {
+        loop {
+            let wait = rate_limiter.lock().unwrap().acquire(operations.len(), Instant::now());
+            match wait {
+                Some(wait) => tokio::time::sleep(wait).await,
+                None => break,
+            }
+        }
+
+        let writes = operations.iter().map(|operation| operation.write.clone()).collect();
+        let can_retry = attempt < max_attempts;
+        match batch_write(auth, writes).await {
+            Ok(response) => {
+                let write_results = response.write_results.unwrap_or_default();
+                let statuses = response.status.unwrap_or_default();
+                let mut retry = Vec::new();
+                for (index, operation) in operations.into_iter().enumerate() {
+                    // A successful status has no code, but a missing status says nothing about the write
+                    let status = match statuses.get(index) {
+                        Some(status) => status,
+                        None => {
+                            operation.complete(Err(FirebaseError::Generic(
+                                "The batchWrite response has no status for the write",
+                            )));
+                            continue;
+                        }
+                    };
+                    match status.code.unwrap_or_default() {
+                        0 => operation.complete(Ok(write_results.get(index).cloned().unwrap_or_default())),
+                        code if can_retry && RETRYABLE_CODES.contains(&code) => retry.push(operation),
+                        _ => {
+                            let error = status_error(status, &operation.write, operation.context());
+                            operation.complete(Err(error));
+                        }
+                    }
+                }
+                operations = retry;
+            }
+            Err(e) if can_retry && is_transient_error(&e) => {}
+            Err(e) => {
+                for operation in operations {
+                    operation.complete(Err(shared_error(&e)));
+                }
+                return;
+            }
+        }
+
+        if !operations.is_empty() {
+            tokio::time::sleep(retry_delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Sends the given writes with a single, non-atomic batchWrite request
+async fn batch_write(auth: &impl FirebaseAuthBearer, writes: Vec<dto::Write>) -> Result<dto::BatchWriteResponse> {
+    let url = firebase_url_action(auth, "batchWrite");
+
+    let resp = auth
+        .client()
+        .post(&url)
+        .bearer_auth(auth.access_token().await)
+        .json(&dto::BatchWriteRequest {
+            writes: Some(writes),
+            ..Default::default()
+        })
+        .send()
+        .await?;
+
+    let resp = extract_google_api_error_async(resp, || "batchWrite".to_owned()).await?;
+
+    Ok(resp.json().await?)
+}
+
+/// Converts the status of the given failed write into an error, like API errors of single writes are converted.
+/// A NOT_FOUND status of a write that requires an existing document is a failed precondition.
+fn status_error(status: &dto::Status, write: &dto::Write, context: String) -> FirebaseError {
+    let message = status.message.clone().unwrap_or_default();
+    match status.code.unwrap_or_default() {
+        5 if requires_existence(&write.current_document) => FirebaseError::PreconditionFailed(message, context),
+        6 => FirebaseError::AlreadyExists(message, context),
+        9 => FirebaseError::PreconditionFailed(message, context),
+        10 => FirebaseError::Aborted(message, context),
+        code => FirebaseError::APIError(http_status_code(code), message, context),
+    }
+}
+
+/// The http status code that corresponds to the given gRPC status code, like the REST API returns it
+fn http_status_code(grpc_code: i32) -> usize {
+    match grpc_code {
+        1 => 499,
+        3 | 9 | 11 => 400,
+        4 => 504,
+        5 => 404,
+        6 | 10 => 409,
+        7 => 403,
+        8 => 429,
+        12 => 501,
+        14 => 503,
+        16 => 401,
+        _ => 500,
+    }
+}
+
+/// Copies an error of a batchWrite request for each of the affected writes.
+/// API errors keep their variant. Other errors, which cannot be copied, become an [`FirebaseError::APIError`].
+fn shared_error(error: &FirebaseError) -> FirebaseError {
+    match error {
+        FirebaseError::APIError(code, message, context) => {
+            FirebaseError::APIError(*code, message.clone(), context.clone())
+        }
+        FirebaseError::UnexpectedResponse(message, status, text, context) => {
+            FirebaseError::UnexpectedResponse(message, *status, text.clone(), context.clone())
+        }
+        FirebaseError::Aborted(message, context) => FirebaseError::Aborted(message.clone(), context.clone()),
+        FirebaseError::PreconditionFailed(message, context) => {
+            FirebaseError::PreconditionFailed(message.clone(), context.clone())
+        }
+        FirebaseError::AlreadyExists(message, context) => {
+            FirebaseError::AlreadyExists(message.clone(), context.clone())
+        }
+        FirebaseError::Generic(message) => FirebaseError::Generic(message),
+        e => FirebaseError::APIError(0, e.to_string(), "batchWrite".to_owned()),
+    }
+}
+
+/// A token bucket that limits the number of writes per second. The rate starts at the initial rate
+/// and grows by 50% every 5 minutes, up to the maximum rate.
+struct RateLimiter {
+    initial_rate: f64,
+    max_rate: f64,
+    start: Instant,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    fn new(initial_ops_per_second: u32, max_ops_per_second: u32, now: Instant) -> Self {
+        let initial_rate = initial_ops_per_second.max(1) as f64;
+        RateLimiter {
+            initial_rate,
+            max_rate: (max_ops_per_second as f64).max(initial_rate),
+            start: now,
+            tokens: initial_rate,
+            last_refill: now,
+        }
+    }
+
+    /// The allowed number of writes per second at the given time
+    fn rate(&self, now: Instant) -> f64 {
+        let ramp_ups = now.duration_since(self.start).as_secs() / (5 * 60);
+        (self.initial_rate * 1.5f64.powi(ramp_ups.min(64) as i32)).min(self.max_rate)
+    }
+
+    /// Takes the given number of tokens. Returns the time to wait before trying again if there are not enough tokens.
+    fn acquire(&mut self, count: usize, now: Instant) -> Option<Duration> {
+        let rate = self.rate(now);
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        // Allow a burst of one second worth of writes, but at least one batch
+        self.tokens = (self.tokens + elapsed * rate).min(rate.max(count as f64));
+        self.last_refill = now;
+
+        let count = count as f64;
+        match self.tokens >= count {
+            true => {
+                self.tokens -= count;
+                None
+            }
+            false => Some(Duration::from_secs_f64((count - self.tokens) / rate)),
+        }
+    }
+}
+
+#[test]
+fn bulk_writer_rate_limiter() {
+    let start = Instant::now();
+    let mut limiter = RateLimiter::new(500, 10_000, start);
+    assert!(limiter.acquire(500, start).is_none());
+    let wait = limiter.acquire(50, start).unwrap();
+    assert_eq!(wait, Duration::from_millis(100));
+    assert!(limiter.acquire(50, start + wait).is_none());
+
+    // 500/50/5: 50% more every 5 minutes
+    assert_eq!(limiter.rate(start + Duration::from_secs(5 * 60)), 750.0);
+    assert_eq!(limiter.rate(start + Duration::from_secs(10 * 60)), 1125.0);
+    assert_eq!(limiter.rate(start + Duration::from_secs(24 * 60 * 60)), 10_000.0);
+}
+
+#[test]
+fn bulk_writer_status_error() {
+    let status = dto::Status {
+        code: Some(9),
+        message: Some("stale".to_owned()),
+        ..Default::default()
+    };
+    let write = dto::Write::default();
+    assert!(matches!(
+        status_error(&status, &write, "doc".to_owned()),
+        FirebaseError::PreconditionFailed(m, c) if m == "stale" && c == "doc"
+    ));
+    let status = dto::Status {
+        code: Some(3),
+        ..Default::default()
+    };
+    assert!(matches!(
+        status_error(&status, &write, "doc".to_owned()),
+        FirebaseError::APIError(400, _, _)
+    ));
+
+    // NOT_FOUND is a failed precondition only for writes that require an existing document
+    let status = dto::Status {
+        code: Some(5),
+        ..Default::default()
+    };
+    assert!(matches!(
+        status_error(&status, &write, "doc".to_owned()),
+        FirebaseError::APIError(404, _, _)
+    ));
+    let write = dto::Write {
+        current_document: precondition(Some(true), None),
+        ..Default::default()
+    };
+    assert!(matches!(
+        status_error(&status, &write, "doc".to_owned()),
+        FirebaseError::PreconditionFailed(_, _)
+    ));
+}
+
+#[test]
+fn bulk_writer_shared_error() {
+    let error = FirebaseError::PreconditionFailed("stale".to_owned(), "batchWrite".to_owned());
+    assert!(matches!(
+        shared_error(&error),
+        FirebaseError::PreconditionFailed(m, c) if m == "stale" && c == "batchWrite"
+    ));
+    let error = FirebaseError::AlreadyExists("exists".to_owned(), "batchWrite".to_owned());
+    assert!(matches!(shared_error(&error), FirebaseError::AlreadyExists(_, _)));
+}
@@ -0,0 +1,193 @@
+use super::*;
+use futures::stream::{self, BoxStream};
+use std::collections::VecDeque;
+
+/// Options for [`list_collection_ids_with_options`]
+#[derive(Default, Clone, Debug)]
+pub struct ListCollectionIdsOptions {
+    /// If set, the collection ids are listed as they were at the given time. This must be a time within the past hour.
+    /// All pages are read from the same consistent snapshot.
+    pub read_time: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+///
+/// Lists the ids of the collections directly below the given document, or at the root of the database
+/// for an empty parent. Subcollections are returned even if the parent document itself does not exist.
+///
+/// The ids are requested page by page while the stream is consumed.
+///
+/// Example:
+/// ```no_run
+/// # use futures::StreamExt;
+/// use firestore_db_and_auth::documents;
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// // All root collections
+/// let mut stream = documents::list_collection_ids(&session, "");
+/// while let Some(collection_id) = stream.next().await {
+///     println!("{}", collection_id.unwrap());
+/// }
+///
+/// // All subcollections of a document
+/// let mut stream = documents::list_collection_ids(&session, "users/abc");
+/// while let Some(collection_id) = stream.next().await {
+///     println!("users/abc/{}", collection_id.unwrap());
+/// }
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'parent' The relative document path, for example "my_collection/document_id". Empty for the database root.
+pub fn list_collection_ids<AUTH>(auth: &AUTH, parent: impl AsRef<str>) -> BoxStream<'static, Result<String>>
+where
+    AUTH: FirebaseAuthBearer + Clone + Send + Sync + 'static,
+{
+    list_collection_ids_with_options(auth, parent, ListCollectionIdsOptions::default())
+}
+
+///
+/// Lists the ids of the collections directly below the given document, or at the root of the database
+/// for an empty parent, like [`list_collection_ids`]. The options allow to list the collections as they were
+/// at a time within the past hour.
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'parent' The relative document path, for example "my_collection/document_id". Empty for the database root.
+/// * 'options' The read time
+pub fn list_collection_ids_with_options<AUTH>(
+    auth: &AUTH,
+    parent: impl AsRef<str>,
+    options: ListCollectionIdsOptions,
+) -> BoxStream<'static, Result<String>>
+where
+    AUTH: FirebaseAuthBearer + Clone + Send + Sync + 'static,
+{
+    let state = CollectionIdsState::new(auth.clone(), parent.as_ref(), options);
+
+    Box::pin(stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        loop {
+            if let Some(collection_id) = state.collection_ids.pop_front() {
+                return Some((Ok(collection_id), Some(state)));
+            }
+            let request = state.next_request()?;
+            match list_collection_ids_page(&state.auth, &state.url, &request, &state.context).await {
+                Ok(response) => state.push_page(response),
+                Err(e) => return Some((Err(e), None)),
+            }
+        }
+    }))
+}
+
+struct CollectionIdsState<AUTH> {
+    auth: AUTH,
+    url: String,
+    context: String,
+    options: ListCollectionIdsOptions,
+    collection_ids: VecDeque<String>,
+    next_page_token: Option<String>,
+    first_page: bool,
+}
+
+impl<AUTH: FirebaseAuthBearer> CollectionIdsState<AUTH> {
+    fn new(auth: AUTH, parent: &str, options: ListCollectionIdsOptions) -> Self {
+        let parent = parent.trim_matches('/');
+        CollectionIdsState {
+            url: firebase_url_parent_action(&auth, parent, "listCollectionIds"),
+            auth,
+            context: parent.to_owned(),
+            options,
+            collection_ids: VecDeque::new(),
+            next_page_token: None,
+            first_page: true,
+        }
+    }
+
+    /// The request for the next page, or None after the last page
+    fn next_request(&mut self) -> Option<dto::ListCollectionIdsRequest> {
+        if !self.first_page && self.next_page_token.is_none() {
+            return None;
+        }
+        self.first_page = false;
+        Some(dto::ListCollectionIdsRequest {
+            page_token: self.next_page_token.take(),
+            read_time: self.options.read_time.as_ref().map(format_timestamp),
+            ..Default::default()
+        })
+    }
+
+    fn push_page(&mut self, response: dto::ListCollectionIdsResponse) {
+        self.collection_ids = response.collection_ids.unwrap_or_default().into();
+        self.next_page_token = response.next_page_token.filter(|token| !token.is_empty());
+    }
+}
+
+async fn list_collection_ids_page(
+    auth: &impl FirebaseAuthBearer,
+    url: &str,
+    request: &dto::ListCollectionIdsRequest,
+    context: &str,
+) -> Result<dto::ListCollectionIdsResponse> {
+    let resp = auth
+        .client()
+        .post(url)
+        .bearer_auth(auth.access_token().await)
+        .json(request)
+        .send()
+        .await?;
+
+    let resp = extract_google_api_error_async(resp, || context.to_owned()).await?;
+
+    Ok(resp.json().await?)
+}
+
+#[test]
+fn list_collection_ids_pages() {
+    let mut state = CollectionIdsState::new(tests::TestAuth::new(), "", Default::default());
+    assert_eq!(
+        state.url,
+        "https://firestore.googleapis.com/v1/projects/project/databases/(default)/documents:listCollectionIds"
+    );
+    let state_below_document = CollectionIdsState::new(tests::TestAuth::new(), "/users/abc/", Default::default());
+    assert!(state_below_document
+        .url
+        .ends_with("/documents/users/abc:listCollectionIds"));
+    assert_eq!(state_below_document.context, "users/abc");
+
+    let request = state.next_request().unwrap();
+    assert!(request.page_token.is_none());
+    assert!(request.read_time.is_none());
+    state.push_page(serde_json::from_str(r#"{"collectionIds": ["a", "b"], "nextPageToken": "page2"}"#).unwrap());
+    assert_eq!(state.collection_ids, ["a", "b"]);
+
+    assert_eq!(state.next_request().unwrap().page_token.as_deref(), Some("page2"));
+    state.push_page(serde_json::from_str(r#"{"collectionIds": ["c"], "nextPageToken": ""}"#).unwrap());
+    assert_eq!(state.collection_ids, ["c"]);
+    assert!(state.next_request().is_none());
+
+    // An empty database has no collection ids at all
+    let mut state = CollectionIdsState::new(tests::TestAuth::new(), "", Default::default());
+    assert!(state.next_request().is_some());
+    state.push_page(serde_json::from_str("{}").unwrap());
+    assert!(state.collection_ids.is_empty());
+    assert!(state.next_request().is_none());
+}
+
+#[test]
+fn list_collection_ids_read_time() {
+    let options = ListCollectionIdsOptions {
+        read_time: Some("2020-04-28T14:52:51.25Z".parse().unwrap()),
+    };
+    let mut state = CollectionIdsState::new(tests::TestAuth::new(), "users/abc", options);
+    let request = serde_json::to_value(state.next_request().unwrap()).unwrap();
+    assert_eq!(request, serde_json::json!({"readTime": "2020-04-28T14:52:51.250000Z"}));
+
+    // Every page is read at the same time
+    state.push_page(serde_json::from_str(r#"{"collectionIds": ["a"], "nextPageToken": "page2"}"#).unwrap());
+    let request = state.next_request().unwrap();
+    assert_eq!(request.read_time.as_deref(), Some("2020-04-28T14:52:51.250000Z"));
+}
@@ -1,39 +1,82 @@
 use super::*;
-use crate::errors::extract_google_api_error_async;
+use crate::errors::extract_google_write_error_async;
+
+/// Delete options for [`delete_with_options`]
+#[derive(Default, Clone, Debug)]
+pub struct DeleteOptions {
+    /// A precondition on the existence of the target document. If set to `Some(true)`,
+    /// the delete fails with [`FirebaseError::PreconditionFailed`] if the document does not exist.
+    pub exists: Option<bool>,
+    /// A precondition on the last update time of the target document. The delete fails with
+    /// [`FirebaseError::PreconditionFailed`] if the document has been changed since the given time.
+    /// Takes precedence over `exists`.
+    pub last_update_time: Option<chrono::DateTime<chrono::Utc>>,
+}
 
 ///
 /// Deletes the document at the given path.
 ///
 /// You cannot use this directly with paths from [`list`] and [`query`] document metadata objects.
-/// Those contain an absolute document path. Use [`abs_to_rel`] to convert to a relative path.
+/// Those contain an absolute document path. Use [`abs_to_rel`] or [`DocumentRef::from_name`] to convert to a relative path.
 ///
 /// ## Arguments
 /// * 'auth' The authentication token
 /// * 'path' The relative collection path and document id, for example "my_collection/document_id"
 /// * 'fail_if_not_existing' If true this method will return an error if the document does not exist.
-pub async fn delete(auth: &impl FirebaseAuthBearer, path: &str, fail_if_not_existing: bool) -> Result<()> {
-    let url = firebase_url(auth.project_id(), path);
-
-    let query_request = dto::Write {
-        current_document: Some(dto::Precondition {
-            exists: match fail_if_not_existing {
-                true => Some(true),
-                false => None,
-            },
-            ..Default::default()
-        }),
+pub async fn delete(auth: &impl FirebaseAuthBearer, path: impl AsRef<str>, fail_if_not_existing: bool) -> Result<()> {
+    let options = DeleteOptions {
+        exists: match fail_if_not_existing {
+            true => Some(true),
+            false => None,
+        },
         ..Default::default()
     };
+    delete_with_options(auth, path, options).await
+}
+
+///
+/// Deletes the document at the given path, if the preconditions of the given options are met.
+///
+/// Example:
+/// ```no_run
+/// # use futures::StreamExt;
+/// use firestore_db_and_auth::documents;
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// // Only delete the document if it has not been changed since it has been read
+/// let mut stream = documents::batch_get(&session, &["tests/a"], documents::ReadOptions::default());
+/// let (_, meta): (serde_json::Value, _) = stream.next().await.unwrap().unwrap().unwrap();
+/// let options = documents::DeleteOptions { last_update_time: meta.update_time, ..Default::default() };
+/// documents::delete_with_options(&session, "tests/a", options).await.unwrap();
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'path' The relative collection path and document id, for example "my_collection/document_id"
+/// * 'options' Delete options like preconditions
+pub async fn delete_with_options(
+    auth: &impl FirebaseAuthBearer,
+    path: impl AsRef<str>,
+    options: DeleteOptions,
+) -> Result<()> {
+    let path = DocumentRef::new(path)?;
+    let url = firebase_url(auth, path.as_ref());
+
+    let precondition = precondition(options.exists, options.last_update_time.as_ref());
 
     let resp = auth
         .client()
         .delete(&url)
+        .query(&precondition_params(&precondition))
         .bearer_auth(auth.access_token().await.to_owned())
-        .json(&query_request)
         .send()
         .await?;
 
-    extract_google_api_error_async(resp, || path.to_owned()).await?;
+    extract_google_write_error_async(resp, requires_existence(&precondition), || path.to_string()).await?;
 
     Ok({})
 }
@@ -0,0 +1,861 @@
+use super::*;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::de::{self, DeserializeOwned, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Serializer;
+use std::collections::{btree_map, BTreeMap};
+use std::fmt;
+
+use crate::firestore_serde::{self, integer_from_u64};
+
+/// The newtype name with which [`FirestoreValue`] asks the [`crate::firestore_serde`] deserializer
+/// to keep timestamp values as such, instead of reading them as rfc3339 strings
+pub(crate) const FIRESTORE_VALUE_TOKEN: &str = "$firestore_db_and_auth::FirestoreValue";
+
+///
+/// An untyped Firestore value.
+///
+/// Use it with [`DynamicDocument`] for documents without a corresponding rust type,
+/// or as the type of a field whose content is not known in advance.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum FirestoreValue {
+    /// A null value. Missing and empty values are read as null as well.
+    #[default]
+    Null,
+    /// A boolean
+    Boolean(bool),
+    /// A signed 64 bit integer
+    Integer(i64),
+    /// A double, including NaN and the infinities
+    Double(f64),
+    /// A timestamp with microsecond precision
+    Timestamp(FirestoreTimestamp),
+    /// A UTF-8 string
+    String(String),
+    /// Raw bytes
+    Bytes(Bytes),
+    /// A reference to another document
+    Reference(DocumentReference),
+    /// A latitude and longitude pair
+    GeoPoint(GeoPoint),
+    /// An array of values
+    Array(Vec<FirestoreValue>),
+    /// A map, ordered by key
+    Map(BTreeMap<String, FirestoreValue>),
+}
+
+impl FirestoreValue {
+    /// Returns true if this is a null value
+    pub fn is_null(&self) -> bool {
+        matches!(self, FirestoreValue::Null)
+    }
+
+    /// Returns the value of a boolean
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FirestoreValue::Boolean(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of an integer
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            FirestoreValue::Integer(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a double, or of an integer converted into a double
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FirestoreValue::Double(v) => Some(*v),
+            FirestoreValue::Integer(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FirestoreValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a timestamp
+    pub fn as_timestamp(&self) -> Option<FirestoreTimestamp> {
+        match self {
+            FirestoreValue::Timestamp(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns the content of a bytes value
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            FirestoreValue::Bytes(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a reference
+    pub fn as_reference(&self) -> Option<&DocumentReference> {
+        match self {
+            FirestoreValue::Reference(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a geo point
+    pub fn as_geo_point(&self) -> Option<GeoPoint> {
+        match self {
+            FirestoreValue::GeoPoint(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns the values of an array
+    pub fn as_array(&self) -> Option<&[FirestoreValue]> {
+        match self {
+            FirestoreValue::Array(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the fields of a map
+    pub fn as_map(&self) -> Option<&BTreeMap<String, FirestoreValue>> {
+        match self {
+            FirestoreValue::Map(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Converts this value into the given type
+    pub fn deserialize_into<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(crate::firestore_serde::from_value(&crate::firestore_serde::to_value(
+            self,
+        )?)?)
+    }
+
+    /// Converts a map into a timestamp, bytes, reference or geo point value if it is the serialized form
+    /// of a [`FirestoreTimestamp`], [`Bytes`], [`DocumentReference`] or [`GeoPoint`]
+    fn from_map(map: BTreeMap<String, FirestoreValue>) -> FirestoreValue {
+        if map.len() == 1 {
+            let (key, content) = map.iter().next().unwrap();
+            let value = match (key.as_str(), content) {
+                (TIMESTAMP_MARKER, FirestoreValue::String(v)) => DateTime::parse_from_rfc3339(v)
+                    .ok()
+                    .map(|v| FirestoreValue::Timestamp(v.with_timezone(&Utc).into())),
+                (BYTES_MARKER, FirestoreValue::String(v)) => {
+                    BASE64_STANDARD.decode(v).ok().map(|v| FirestoreValue::Bytes(v.into()))
+                }
+                (REFERENCE_MARKER, FirestoreValue::String(v)) => DocumentReference::from_name(v.as_str())
+                    .ok()
+                    .map(FirestoreValue::Reference),
+                (GEO_POINT_MARKER, FirestoreValue::Map(point)) => {
+                    let coordinate = |name: &str| point.get(name).map_or(Some(0.0), FirestoreValue::as_f64);
+                    Some(FirestoreValue::GeoPoint(GeoPoint::new(
+                        coordinate("latitude").unwrap_or_default(),
+                        coordinate("longitude").unwrap_or_default(),
+                    )))
+                }
+                _ => None,
+            };
+            if let Some(value) = value {
+                return value;
+            }
+        }
+        FirestoreValue::Map(map)
+    }
+}
+
+impl Serialize for FirestoreValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            FirestoreValue::Null => serializer.serialize_unit(),
+            FirestoreValue::Boolean(v) => serializer.serialize_bool(*v),
+            FirestoreValue::Integer(v) => serializer.serialize_i64(*v),
+            FirestoreValue::Double(v) => serializer.serialize_f64(*v),
+            FirestoreValue::Timestamp(v) => v.serialize(serializer),
+            FirestoreValue::String(v) => serializer.serialize_str(v),
+            FirestoreValue::Bytes(v) => v.serialize(serializer),
+            FirestoreValue::Reference(v) => v.serialize(serializer),
+            FirestoreValue::GeoPoint(v) => v.serialize(serializer),
+            FirestoreValue::Array(v) => serializer.collect_seq(v),
+            FirestoreValue::Map(v) => serializer.collect_map(v),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for FirestoreValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(FIRESTORE_VALUE_TOKEN, FirestoreValueVisitor)
+    }
+}
+
+struct FirestoreValueVisitor;
+
+impl<'de> Visitor<'de> for FirestoreValueVisitor {
+    type Value = FirestoreValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a Firestore value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> std::result::Result<FirestoreValue, E> {
+        Ok(FirestoreValue::Boolean(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<FirestoreValue, E> {
+        Ok(FirestoreValue::Integer(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<FirestoreValue, E> {
+        integer_from_u64(v).map(FirestoreValue::Integer).map_err(E::custom)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> std::result::Result<FirestoreValue, E> {
+        Ok(FirestoreValue::Double(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<FirestoreValue, E> {
+        Ok(FirestoreValue::String(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> std::result::Result<FirestoreValue, E> {
+        Ok(FirestoreValue::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<FirestoreValue, E> {
+        Ok(FirestoreValue::Bytes(v.to_vec().into()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> std::result::Result<FirestoreValue, E> {
+        Ok(FirestoreValue::Bytes(v.into()))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<FirestoreValue, E> {
+        Ok(FirestoreValue::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> std::result::Result<FirestoreValue, E> {
+        Ok(FirestoreValue::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<FirestoreValue, D::Error> {
+        FirestoreValue::deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<FirestoreValue, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<FirestoreValue, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(FirestoreValue::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<FirestoreValue, A::Error> {
+        let mut fields = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry()? {
+            fields.insert(key, value);
+        }
+        Ok(FirestoreValue::from_map(fields))
+    }
+}
+
+macro_rules! from_integer {
+    ($($type:ty),*) => {
+        $(
+            impl From<$type> for FirestoreValue {
+                fn from(v: $type) -> Self {
+                    FirestoreValue::Integer(v.into())
+                }
+            }
+        )*
+    };
+}
+
+from_integer!(i8, i16, i32, i64, u8, u16, u32);
+
+/// Firestore integers are signed 64 bit integers. Values above `i64::MAX` are an error.
+impl TryFrom<u64> for FirestoreValue {
+    type Error = FirebaseError;
+
+    fn try_from(v: u64) -> Result<Self> {
+        Ok(FirestoreValue::Integer(integer_from_u64(v)?))
+    }
+}
+
+impl From<f32> for FirestoreValue {
+    fn from(v: f32) -> Self {
+        FirestoreValue::Double(v.into())
+    }
+}
+
+impl From<f64> for FirestoreValue {
+    fn from(v: f64) -> Self {
+        FirestoreValue::Double(v)
+    }
+}
+
+impl From<bool> for FirestoreValue {
+    fn from(v: bool) -> Self {
+        FirestoreValue::Boolean(v)
+    }
+}
+
+impl From<&str> for FirestoreValue {
+    fn from(v: &str) -> Self {
+        FirestoreValue::String(v.to_owned())
+    }
+}
+
+impl From<String> for FirestoreValue {
+    fn from(v: String) -> Self {
+        FirestoreValue::String(v)
+    }
+}
+
+impl From<FirestoreTimestamp> for FirestoreValue {
+    fn from(v: FirestoreTimestamp) -> Self {
+        FirestoreValue::Timestamp(v)
+    }
+}
+
+impl From<DateTime<Utc>> for FirestoreValue {
+    fn from(v: DateTime<Utc>) -> Self {
+        FirestoreValue::Timestamp(v.into())
+    }
+}
+
+impl From<Bytes> for FirestoreValue {
+    fn from(v: Bytes) -> Self {
+        FirestoreValue::Bytes(v)
+    }
+}
+
+impl From<DocumentReference> for FirestoreValue {
+    fn from(v: DocumentReference) -> Self {
+        FirestoreValue::Reference(v)
+    }
+}
+
+impl From<GeoPoint> for FirestoreValue {
+    fn from(v: GeoPoint) -> Self {
+        FirestoreValue::GeoPoint(v)
+    }
+}
+
+impl<T: Into<FirestoreValue>> From<Vec<T>> for FirestoreValue {
+    fn from(v: Vec<T>) -> Self {
+        FirestoreValue::Array(v.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<FirestoreValue>> From<Option<T>> for FirestoreValue {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(FirestoreValue::Null, Into::into)
+    }
+}
+
+impl From<BTreeMap<String, FirestoreValue>> for FirestoreValue {
+    fn from(v: BTreeMap<String, FirestoreValue>) -> Self {
+        FirestoreValue::Map(v)
+    }
+}
+
+impl From<DynamicDocument> for FirestoreValue {
+    fn from(v: DynamicDocument) -> Self {
+        FirestoreValue::Map(v.fields)
+    }
+}
+
+/// Converts a json value. The serialized forms of [`FirestoreTimestamp`], [`Bytes`], [`DocumentReference`]
+/// and [`GeoPoint`] are converted into the respective values.
+/// Integers above `i64::MAX` do not fit into a Firestore integer and are an error, which contains the path of the field.
+impl TryFrom<serde_json::Value> for FirestoreValue {
+    type Error = FirebaseError;
+
+    fn try_from(v: serde_json::Value) -> Result<Self> {
+        Ok(from_json(v)?)
+    }
+}
+
+fn from_json(v: serde_json::Value) -> std::result::Result<FirestoreValue, firestore_serde::Error> {
+    let value = match v {
+        serde_json::Value::Null => FirestoreValue::Null,
+        serde_json::Value::Bool(v) => FirestoreValue::Boolean(v),
+        serde_json::Value::Number(v) => match (v.as_i64(), v.as_u64()) {
+            (Some(v), _) => FirestoreValue::Integer(v),
+            (None, Some(v)) => FirestoreValue::Integer(integer_from_u64(v)?),
+            _ => FirestoreValue::Double(v.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(v) => FirestoreValue::String(v),
+        serde_json::Value::Array(v) => FirestoreValue::Array(
+            v.into_iter()
+                .enumerate()
+                .map(|(index, v)| from_json(v).map_err(|e| e.at_index(index)))
+                .collect::<std::result::Result<_, _>>()?,
+        ),
+        serde_json::Value::Object(v) => FirestoreValue::from_map(
+            v.into_iter()
+                .map(|(k, v)| {
+                    let value = from_json(v).map_err(|e| e.in_field(&k))?;
+                    Ok((k, value))
+                })
+                .collect::<std::result::Result<_, _>>()?,
+        ),
+    };
+    Ok(value)
+}
+
+/// Converts into a json value. Timestamps, bytes, references and geo points are converted into the serialized forms
+/// of [`FirestoreTimestamp`], [`Bytes`], [`DocumentReference`] and [`GeoPoint`]. NaN and infinite doubles become `null`.
+impl From<FirestoreValue> for serde_json::Value {
+    fn from(v: FirestoreValue) -> Self {
+        match v {
+            FirestoreValue::Null => serde_json::Value::Null,
+            FirestoreValue::Boolean(v) => v.into(),
+            FirestoreValue::Integer(v) => v.into(),
+            FirestoreValue::Double(v) => v.into(),
+            FirestoreValue::Timestamp(v) => marker(TIMESTAMP_MARKER, format_timestamp(&v.datetime())),
+            FirestoreValue::String(v) => v.into(),
+            FirestoreValue::Bytes(v) => marker(BYTES_MARKER, BASE64_STANDARD.encode(&v.0)),
+            FirestoreValue::Reference(v) => marker(REFERENCE_MARKER, v.name()),
+            FirestoreValue::GeoPoint(v) => marker(
+                GEO_POINT_MARKER,
+                serde_json::json!({"latitude": v.latitude, "longitude": v.longitude}),
+            ),
+            FirestoreValue::Array(v) => serde_json::Value::Array(v.into_iter().map(Into::into).collect()),
+            FirestoreValue::Map(v) => serde_json::Value::Object(v.into_iter().map(|(k, v)| (k, v.into())).collect()),
+        }
+    }
+}
+
+///
+/// An untyped document, for documents without a corresponding rust type.
+///
+/// Fields are accessed with dotted field paths like "address.city", see [`FieldPath`].
+/// Read and write it like any other document type, or create it with the [`crate::firestore_doc`] macro.
+///
+/// Example:
+/// ```
+/// use firestore_db_and_auth::documents::{DynamicDocument, FirestoreTimestamp};
+/// use firestore_db_and_auth::firestore_doc;
+///
+/// let mut doc = firestore_doc! {
+///     "name": "Alice",
+///     "visits": 3,
+///     "address": { "city": "Berlin", "lines": ["Street 1", null] },
+///     "joined": FirestoreTimestamp::now(),
+/// };
+/// assert_eq!(doc.get_str("address.city"), Some("Berlin"));
+/// assert_eq!(doc.get_i64("visits"), Some(3));
+///
+/// doc.set("address.zip", "10115")?;
+/// doc.remove("visits");
+/// for (field_name, value) in &doc {
+///     println!("{}: {:?}", field_name, value);
+/// }
+/// # Ok::<(), firestore_db_and_auth::errors::FirebaseError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DynamicDocument {
+    fields: BTreeMap<String, FirestoreValue>,
+}
+
+impl DynamicDocument {
+    /// An empty document
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The top-level fields of this document
+    pub fn fields(&self) -> &BTreeMap<String, FirestoreValue> {
+        &self.fields
+    }
+
+    /// Consumes this document and returns its top-level fields
+    pub fn into_fields(self) -> BTreeMap<String, FirestoreValue> {
+        self.fields
+    }
+
+    /// The number of top-level fields
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns true if this document has no fields
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Iterates over the top-level fields, ordered by field name
+    pub fn iter(&self) -> btree_map::Iter<'_, String, FirestoreValue> {
+        self.fields.iter()
+    }
+
+    /// Returns the value at the given field path, like "address.city".
+    /// Returns None if the field does not exist or if the path is invalid.
+    pub fn get(&self, path: &str) -> Option<&FirestoreValue> {
+        let path: FieldPath = path.parse().ok()?;
+        let (last, parents) = path.field_names().split_last()?;
+        let mut fields = &self.fields;
+        for name in parents {
+            fields = fields.get(name)?.as_map()?;
+        }
+        fields.get(last)
+    }
+
+    /// Returns the value at the given field path for modification
+    pub fn get_mut(&mut self, path: &str) -> Option<&mut FirestoreValue> {
+        let path: FieldPath = path.parse().ok()?;
+        let (last, parents) = path.field_names().split_last()?;
+        let mut fields = &mut self.fields;
+        for name in parents {
+            fields = match fields.get_mut(name)? {
+                FirestoreValue::Map(map) => map,
+                _ => return None,
+            };
+        }
+        fields.get_mut(last)
+    }
+
+    /// Returns true if a value exists at the given field path
+    pub fn contains(&self, path: &str) -> bool {
+        self.get(path).is_some()
+    }
+
+    /// Sets the value at the given field path and returns the previous value.
+    /// Missing maps along the path are created, other values along the path are replaced by maps.
+    pub fn set(&mut self, path: &str, value: impl Into<FirestoreValue>) -> Result<Option<FirestoreValue>> {
+        let path: FieldPath = path.parse()?;
+        let (last, parents) = path
+            .field_names()
+            .split_last()
+            .ok_or(FirebaseError::Generic("Empty field name in field path"))?;
+        let mut fields = &mut self.fields;
+        for name in parents {
+            let value = fields.entry(name.clone()).or_default();
+            if !matches!(value, FirestoreValue::Map(_)) {
+                *value = FirestoreValue::Map(BTreeMap::new());
+            }
+            fields = match value {
+                FirestoreValue::Map(map) => map,
+                _ => unreachable!(),
+            };
+        }
+        Ok(fields.insert(last.clone(), value.into()))
+    }
+
+    /// Sets the given top-level field. The field name is used as is and not parsed as a field path.
+    pub fn insert(
+        &mut self,
+        field_name: impl Into<String>,
+        value: impl Into<FirestoreValue>,
+    ) -> Option<FirestoreValue> {
+        self.fields.insert(field_name.into(), value.into())
+    }
+
+    /// Removes the value at the given field path and returns it
+    pub fn remove(&mut self, path: &str) -> Option<FirestoreValue> {
+        let path: FieldPath = path.parse().ok()?;
+        let (last, parents) = path.field_names().split_last()?;
+        let mut fields = &mut self.fields;
+        for name in parents {
+            fields = match fields.get_mut(name)? {
+                FirestoreValue::Map(map) => map,
+                _ => return None,
+            };
+        }
+        fields.remove(last)
+    }
+
+    /// Returns the boolean at the given field path
+    pub fn get_bool(&self, path: &str) -> Option<bool> {
+        self.get(path)?.as_bool()
+    }
+
+    /// Returns the integer at the given field path
+    pub fn get_i64(&self, path: &str) -> Option<i64> {
+        self.get(path)?.as_i64()
+    }
+
+    /// Returns the double or integer at the given field path as a double
+    pub fn get_f64(&self, path: &str) -> Option<f64> {
+        self.get(path)?.as_f64()
+    }
+
+    /// Returns the string at the given field path
+    pub fn get_str(&self, path: &str) -> Option<&str> {
+        self.get(path)?.as_str()
+    }
+
+    /// Returns the timestamp at the given field path
+    pub fn get_timestamp(&self, path: &str) -> Option<FirestoreTimestamp> {
+        self.get(path)?.as_timestamp()
+    }
+
+    /// Returns the array at the given field path
+    pub fn get_array(&self, path: &str) -> Option<&[FirestoreValue]> {
+        self.get(path)?.as_array()
+    }
+
+    /// Returns the map at the given field path
+    pub fn get_map(&self, path: &str) -> Option<&BTreeMap<String, FirestoreValue>> {
+        self.get(path)?.as_map()
+    }
+
+    /// Converts the value at the given field path into the given type. Returns None if the field does not exist.
+    pub fn get_as<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>> {
+        self.get(path).map(FirestoreValue::deserialize_into).transpose()
+    }
+
+    /// Converts this document into the given type
+    pub fn deserialize_into<T: DeserializeOwned>(&self) -> Result<T> {
+        let value = crate::firestore_serde::to_value(self)?;
+        Ok(crate::firestore_serde::from_value(&value)?)
+    }
+}
+
+impl<'a> IntoIterator for &'a DynamicDocument {
+    type Item = (&'a String, &'a FirestoreValue);
+    type IntoIter = btree_map::Iter<'a, String, FirestoreValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.iter()
+    }
+}
+
+impl IntoIterator for DynamicDocument {
+    type Item = (String, FirestoreValue);
+    type IntoIter = btree_map::IntoIter<String, FirestoreValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.into_iter()
+    }
+}
+
+impl<K: Into<String>, V: Into<FirestoreValue>> FromIterator<(K, V)> for DynamicDocument {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        DynamicDocument {
+            fields: iter.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+        }
+    }
+}
+
+impl From<BTreeMap<String, FirestoreValue>> for DynamicDocument {
+    fn from(fields: BTreeMap<String, FirestoreValue>) -> Self {
+        DynamicDocument { fields }
+    }
+}
+
+/// Converts a json object. Returns an error for any other json value.
+impl TryFrom<serde_json::Value> for DynamicDocument {
+    type Error = FirebaseError;
+
+    fn try_from(v: serde_json::Value) -> Result<Self> {
+        match FirestoreValue::try_from(v)? {
+            FirestoreValue::Map(fields) => Ok(DynamicDocument { fields }),
+            _ => Err(FirebaseError::Generic("A document must be a json object")),
+        }
+    }
+}
+
+impl From<DynamicDocument> for serde_json::Value {
+    fn from(v: DynamicDocument) -> Self {
+        FirestoreValue::Map(v.fields).into()
+    }
+}
+
+///
+/// Creates a [`documents::DynamicDocument`](crate::documents::DynamicDocument) with a json like syntax.
+///
+/// Keys are string literals. Values are `null`, nested `{...}` maps, `[...]` arrays
+/// or any expression that converts into a [`documents::FirestoreValue`](crate::documents::FirestoreValue).
+///
+/// Example:
+/// ```
+/// use firestore_db_and_auth::{firestore_doc, documents::GeoPoint};
+///
+/// let city = "Berlin";
+/// let doc = firestore_doc! {
+///     "city": city,
+///     "population": 3_700_000,
+///     "location": GeoPoint::new(52.52, 13.40),
+///     "districts": [{ "name": "Mitte" }, { "name": "Pankow" }],
+///     "mayor": null,
+/// };
+/// assert_eq!(doc.get_array("districts").unwrap().len(), 2);
+/// ```
+#[macro_export]
+macro_rules! firestore_doc {
+    ($($tt:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut document = $crate::documents::DynamicDocument::new();
+        $crate::__firestore_entries!(@map document $($tt)*);
+        document
+    }};
+}
+
+/// Creates a [`documents::FirestoreValue`](crate::documents::FirestoreValue) with the syntax of [`firestore_doc`]
+#[macro_export]
+macro_rules! firestore_value {
+    (null) => {
+        $crate::documents::FirestoreValue::Null
+    };
+    ([$($tt:tt)*]) => {{
+        #[allow(unused_mut)]
+        let mut values = ::std::vec::Vec::new();
+        $crate::__firestore_entries!(@array values $($tt)*);
+        $crate::documents::FirestoreValue::Array(values)
+    }};
+    ({$($tt:tt)*}) => {
+        $crate::documents::FirestoreValue::from($crate::firestore_doc!($($tt)*))
+    };
+    ($value:expr) => {
+        $crate::documents::FirestoreValue::from($value)
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __firestore_entries {
+    (@map $map:ident) => {};
+    (@map $map:ident $key:literal : null $(, $($rest:tt)*)?) => {
+        $map.insert($key, $crate::firestore_value!(null));
+        $crate::__firestore_entries!(@map $map $($($rest)*)?);
+    };
+    (@map $map:ident $key:literal : [$($value:tt)*] $(, $($rest:tt)*)?) => {
+        $map.insert($key, $crate::firestore_value!([$($value)*]));
+        $crate::__firestore_entries!(@map $map $($($rest)*)?);
+    };
+    (@map $map:ident $key:literal : {$($value:tt)*} $(, $($rest:tt)*)?) => {
+        $map.insert($key, $crate::firestore_value!({$($value)*}));
+        $crate::__firestore_entries!(@map $map $($($rest)*)?);
+    };
+    (@map $map:ident $key:literal : $value:expr $(, $($rest:tt)*)?) => {
+        $map.insert($key, $crate::firestore_value!($value));
+        $crate::__firestore_entries!(@map $map $($($rest)*)?);
+    };
+    (@array $values:ident) => {};
+    (@array $values:ident null $(, $($rest:tt)*)?) => {
+        $values.push($crate::firestore_value!(null));
+        $crate::__firestore_entries!(@array $values $($($rest)*)?);
+    };
+    (@array $values:ident [$($value:tt)*] $(, $($rest:tt)*)?) => {
+        $values.push($crate::firestore_value!([$($value)*]));
+        $crate::__firestore_entries!(@array $values $($($rest)*)?);
+    };
+    (@array $values:ident {$($value:tt)*} $(, $($rest:tt)*)?) => {
+        $values.push($crate::firestore_value!({$($value)*}));
+        $crate::__firestore_entries!(@array $values $($($rest)*)?);
+    };
+    (@array $values:ident $value:expr $(, $($rest:tt)*)?) => {
+        $values.push($crate::firestore_value!($value));
+        $crate::__firestore_entries!(@array $values $($($rest)*)?);
+    };
+}
+
+#[test]
+fn dynamic_document_paths() {
+    let mut doc = crate::firestore_doc! {
+        "name": "Alice",
+        "age": 30,
+        "score": -1.5,
+        "address": { "city": "Berlin", "odd.key": true },
+        "tags": ["a", 2, null, { "b": [] }],
+        "nothing": null,
+    };
+    assert_eq!(doc.len(), 6);
+    assert_eq!(doc.get_str("name"), Some("Alice"));
+    assert_eq!(doc.get_i64("age"), Some(30));
+    assert_eq!(doc.get_f64("age"), Some(30.0));
+    assert_eq!(doc.get_f64("score"), Some(-1.5));
+    assert_eq!(doc.get_str("address.city"), Some("Berlin"));
+    assert_eq!(doc.get_bool("address.`odd.key`"), Some(true));
+    assert_eq!(doc.get_array("tags").unwrap()[2], FirestoreValue::Null);
+    assert!(doc.get("nothing").unwrap().is_null());
+    assert!(doc.get("name.first").is_none());
+    assert!(doc.get("a..b").is_none());
+
+    assert_eq!(doc.set("address.zip", "10115").unwrap(), None);
+    assert_eq!(doc.get_map("address").unwrap().len(), 3);
+    assert_eq!(doc.set("name.first", "Alice").unwrap(), None);
+    assert_eq!(doc.get_str("name.first"), Some("Alice"));
+    assert!(doc.set("a..b", 1).is_err());
+    *doc.get_mut("age").unwrap() = 31.into();
+    assert_eq!(doc.remove("age"), Some(FirestoreValue::Integer(31)));
+    assert_eq!(doc.remove("address.city"), Some("Berlin".into()));
+    assert!(!doc.contains("address.city"));
+
+    let names: Vec<&String> = doc.iter().map(|(name, _)| name).collect();
+    assert_eq!(names, ["address", "name", "nothing", "score", "tags"]);
+
+    #[derive(Deserialize)]
+    struct Address {
+        zip: String,
+    }
+    let address: Address = doc.get_as("address").unwrap().unwrap();
+    assert_eq!(address.zip, "10115");
+}
+
+#[test]
+fn dynamic_document_conversions() -> Result<()> {
+    use crate::firebase_rest_to_rust::{document_to_pod, pod_to_document};
+
+    let timestamp: FirestoreTimestamp = "2020-04-28T14:52:51.250511Z".parse::<DateTime<Utc>>().unwrap().into();
+    let doc = crate::firestore_doc! {
+        "at": timestamp,
+        "data": Bytes(vec![1, 2, 3]),
+        "location": GeoPoint::new(1.5, 2.0),
+        "owner": DocumentReference::from_name("projects/p/databases/(default)/documents/users/abc")?,
+        "nested": { "ratio": f64::INFINITY, "items": [1, "x"] },
+        "big": i64::MAX,
+    };
+
+    // Through the wire format of the REST API
+    let document = pod_to_document(&doc)?;
+    let fields = document.fields.as_ref().unwrap();
+    assert_eq!(
+        fields["at"].timestamp_value.as_deref(),
+        Some("2020-04-28T14:52:51.250511Z")
+    );
+    assert_eq!(fields["data"].bytes_value.as_deref(), Some("AQID"));
+    let document: dto::Document = serde_json::from_value(serde_json::to_value(&document)?)?;
+    let read: DynamicDocument = document_to_pod(&document, None)?;
+    assert_eq!(read, doc);
+    assert_eq!(read.get_timestamp("at"), Some(timestamp));
+
+    // Through json
+    let json = serde_json::Value::from(doc.clone());
+    assert_eq!(json["nested"]["items"], serde_json::json!([1, "x"]));
+    assert_eq!(DynamicDocument::try_from(json)?.get_timestamp("at"), Some(timestamp));
+    assert!(DynamicDocument::try_from(serde_json::json!([1])).is_err());
+    assert!(FirestoreValue::try_from(u64::MAX).is_err());
+    let err = DynamicDocument::try_from(serde_json::json!({"a": [1, u64::MAX]})).unwrap_err();
+    assert!(err.to_string().contains("at 'a[1]'"));
+    assert!(serde_json::from_str::<DynamicDocument>(&format!(r#"{{"big": {}}}"#, u64::MAX)).is_err());
+    let from_json: DynamicDocument = serde_json::from_str(r#"{"a": {"b": [1.5, null]}}"#)?;
+    assert_eq!(from_json.get_array("a.b").unwrap()[0], FirestoreValue::Double(1.5));
+
+    #[derive(Deserialize)]
+    struct Typed {
+        at: FirestoreTimestamp,
+        location: GeoPoint,
+    }
+    let typed: Typed = read.deserialize_into()?;
+    assert_eq!(typed.at, timestamp);
+    assert_eq!(typed.location, GeoPoint::new(1.5, 2.0));
+    Ok(())
+}
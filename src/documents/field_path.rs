@@ -0,0 +1,152 @@
+use super::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+///
+/// A path to a field within a document, like "address.city" for the field "city" of the map "address".
+///
+/// Field names that are not simple names (letters, digits and underscores, not starting with a digit)
+/// are quoted with backticks, for example "`first.name`" for a field that contains a dot.
+/// Use [`FieldPath::new`] to create a correctly escaped path from field names
+/// and [`FieldPath::from_str`] to parse a dotted path.
+///
+/// Example:
+/// ```
+/// use firestore_db_and_auth::documents::FieldPath;
+///
+/// let path = FieldPath::new(["users", "first.name"]);
+/// assert_eq!(path.to_string(), "users.`first.name`");
+/// let parsed: FieldPath = "users.`first.name`".parse().unwrap();
+/// assert_eq!(parsed, path);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FieldPath(Vec<String>);
+
+impl FieldPath {
+    /// Creates a field path from the given field names, which are escaped if necessary
+    pub fn new<S: Into<String>>(field_names: impl IntoIterator<Item = S>) -> Self {
+        FieldPath(field_names.into_iter().map(Into::into).collect())
+    }
+
+    /// The unescaped field names of this path
+    pub fn field_names(&self) -> &[String] {
+        &self.0
+    }
+
+    /// Returns the path of the given field within the map of this path
+    pub fn child(&self, field_name: impl Into<String>) -> Self {
+        let mut field_names = self.0.clone();
+        field_names.push(field_name.into());
+        FieldPath(field_names)
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let escaped: Vec<String> = self.0.iter().map(|name| escape_field_name(name)).collect();
+        write!(f, "{}", escaped.join("."))
+    }
+}
+
+impl FromStr for FieldPath {
+    type Err = FirebaseError;
+
+    /// Parses a dotted field path like "a.b.c". Field names can be quoted with backticks.
+    fn from_str(path: &str) -> Result<Self> {
+        let mut field_names = Vec::new();
+        let mut chars = path.chars().peekable();
+        loop {
+            let mut field_name = String::new();
+            match chars.next() {
+                Some('`') => loop {
+                    match chars.next() {
+                        Some('\\') => field_name.extend(chars.next()),
+                        Some('`') => break,
+                        Some(c) => field_name.push(c),
+                        None => return Err(FirebaseError::Generic("Unterminated backtick in field path")),
+                    }
+                },
+                Some(c) if c != '.' => {
+                    field_name.push(c);
+                    while let Some(c) = chars.next_if(|c| *c != '.' && *c != '`') {
+                        field_name.push(c);
+                    }
+                }
+                _ => return Err(FirebaseError::Generic("Empty field name in field path")),
+            }
+            field_names.push(field_name);
+
+            match chars.next() {
+                Some('.') => continue,
+                None => return Ok(FieldPath(field_names)),
+                Some(_) => return Err(FirebaseError::Generic("Invalid character in field path")),
+            }
+        }
+    }
+}
+
+/// Quotes a field name with backticks if it is not a simple field name, as required for field paths.
+pub(crate) fn escape_field_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let simple = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    match simple {
+        true => name.to_owned(),
+        false => format!("`{}`", name.replace('\\', "\\\\").replace('`', "\\`")),
+    }
+}
+
+/// Returns the escaped field paths of the given document fields. With `deep` set, the paths of all
+/// leaf fields of nested maps are returned instead of the top-level field names.
+pub(crate) fn document_field_paths(fields: &HashMap<String, dto::Value>, deep: bool) -> Vec<String> {
+    let mut field_paths = Vec::new();
+    collect_field_paths(fields, &FieldPath(Vec::new()), deep, &mut field_paths);
+    field_paths.sort();
+    field_paths
+}
+
+fn collect_field_paths(
+    fields: &HashMap<String, dto::Value>,
+    parent: &FieldPath,
+    deep: bool,
+    field_paths: &mut Vec<String>,
+) {
+    for (name, value) in fields {
+        let path = parent.child(name.as_str());
+        match value.map_value.as_ref().and_then(|map| map.fields.as_ref()) {
+            Some(inner) if deep && !inner.is_empty() => collect_field_paths(inner, &path, deep, field_paths),
+            _ => field_paths.push(path.to_string()),
+        }
+    }
+}
+
+#[test]
+fn field_path_parse_and_escape() {
+    let path: FieldPath = "a.`b.c`.`d\\`e`.f_1".parse().unwrap();
+    assert_eq!(path.field_names(), ["a", "b.c", "d`e", "f_1"]);
+    assert_eq!(path.to_string(), "a.`b.c`.`d\\`e`.f_1");
+
+    let path: FieldPath = "a b.c".parse().unwrap();
+    assert_eq!(path.field_names(), ["a b", "c"]);
+    assert_eq!(path.to_string(), "`a b`.c");
+
+    assert!("a..b".parse::<FieldPath>().is_err());
+    assert!("a.`b".parse::<FieldPath>().is_err());
+    assert!("`a`b".parse::<FieldPath>().is_err());
+    assert!("".parse::<FieldPath>().is_err());
+    assert_eq!(escape_field_name("1st"), "`1st`");
+}
+
+#[test]
+fn field_paths_of_nested_document() {
+    let document = crate::firebase_rest_to_rust::pod_to_document(&serde_json::json!({
+        "a": {"b": 1, "c.d": {"e": true}},
+        "empty": {},
+        "f": "x"
+    }))
+    .unwrap();
+    let fields = document.fields.unwrap();
+    assert_eq!(document_field_paths(&fields, false), ["a", "empty", "f"]);
+    assert_eq!(document_field_paths(&fields, true), ["a.`c.d`.e", "a.b", "empty", "f"]);
+}
@@ -0,0 +1,167 @@
+use super::*;
+use crate::firebase_rest_to_rust::serde_value_to_firebase_value;
+
+/// The key of the JSON object that a [`FieldValue`] serializes to
+pub(crate) const FIELD_VALUE_MARKER: &str = "__firestore_field_value__";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", content = "value", rename_all = "snake_case")]
+enum FieldValueKind {
+    ServerTimestamp,
+    Increment(serde_json::Value),
+    Maximum(serde_json::Value),
+    Minimum(serde_json::Value),
+    ArrayUnion(Vec<serde_json::Value>),
+    ArrayRemove(Vec<serde_json::Value>),
+    Delete,
+}
+
+///
+/// A sentinel value that is not stored as is, but transformed by the Firestore server.
+///
+/// Field values can be embedded in a serializable document or passed alongside a document
+/// via [`WriteOptions::transforms`]. Embedded field values are removed from the document fields
+/// and applied as field transforms after the document has been written.
+/// The resulting server values are returned in [`WriteResult::transform_results`].
+///
+/// Field values cannot be used within arrays.
+///
+/// Example:
+/// ```no_run
+/// use serde::Serialize;
+/// use firestore_db_and_auth::documents::{self, FieldValue};
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+///
+/// #[derive(Serialize)]
+/// struct Visit {
+///     last_visit: FieldValue,
+///     visits: FieldValue,
+/// }
+///
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+/// let visit = Visit {
+///     last_visit: FieldValue::server_timestamp(),
+///     visits: FieldValue::increment(1),
+/// };
+/// let options = documents::WriteOptions { merge: true, ..Default::default() };
+/// let result = documents::write(&session, "counters", Some("visits"), &visit, options).await?;
+/// println!("Visits: {}", result.transform_results["visits"]);
+/// # Ok::<(), firestore_db_and_auth::errors::FirebaseError>(())
+/// # });
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldValue {
+    #[serde(rename = "__firestore_field_value__")]
+    kind: FieldValueKind,
+}
+
+impl FieldValue {
+    /// Sets the field to the time at which the server processed the write
+    pub fn server_timestamp() -> Self {
+        FieldValue {
+            kind: FieldValueKind::ServerTimestamp,
+        }
+    }
+
+    /// Adds the given number to the field value. A missing or non-numeric field is set to the given number.
+    pub fn increment(n: impl Into<serde_json::Value>) -> Self {
+        FieldValue {
+            kind: FieldValueKind::Increment(n.into()),
+        }
+    }
+
+    /// Sets the field to the maximum of its current value and the given number
+    pub fn maximum(n: impl Into<serde_json::Value>) -> Self {
+        FieldValue {
+            kind: FieldValueKind::Maximum(n.into()),
+        }
+    }
+
+    /// Sets the field to the minimum of its current value and the given number
+    pub fn minimum(n: impl Into<serde_json::Value>) -> Self {
+        FieldValue {
+            kind: FieldValueKind::Minimum(n.into()),
+        }
+    }
+
+    /// Appends the given elements to the array field, if they are not already present
+    pub fn array_union<V: Into<serde_json::Value>>(elements: impl IntoIterator<Item = V>) -> Self {
+        FieldValue {
+            kind: FieldValueKind::ArrayUnion(elements.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    /// Removes all instances of the given elements from the array field
+    pub fn array_remove<V: Into<serde_json::Value>>(elements: impl IntoIterator<Item = V>) -> Self {
+        FieldValue {
+            kind: FieldValueKind::ArrayRemove(elements.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    /// Removes the field from the target document. This requires a merge write
+    /// or an update mask, see [`WriteOptions::merge`] and [`WriteOptions::update_mask`].
+    pub fn delete() -> Self {
+        FieldValue {
+            kind: FieldValueKind::Delete,
+        }
+    }
+
+    /// Returns the field value if the given Firestore value is the serialized form of a field value
+    pub(crate) fn from_firebase_value(value: &dto::Value) -> Result<Option<FieldValue>> {
+        match value.map_value.as_ref().and_then(|map| map.fields.as_ref()) {
+            Some(fields) if fields.len() == 1 => match fields.get(FIELD_VALUE_MARKER) {
+                Some(kind) => Ok(Some(FieldValue {
+                    kind: crate::firestore_serde::from_value(kind)?,
+                })),
+                None => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+
+    /// Converts this field value into a field transform for the given field path.
+    /// Returns None for [`FieldValue::delete`], which is not a field transform.
+    pub(crate) fn to_field_transform(&self, field_path: String) -> Option<dto::FieldTransform> {
+        let array = |elements: &Vec<serde_json::Value>| dto::ArrayValue {
+            values: Some(elements.iter().map(serde_value_to_firebase_value).collect()),
+        };
+
+        let mut transform = dto::FieldTransform {
+            field_path: Some(field_path),
+            ..Default::default()
+        };
+        match &self.kind {
+            FieldValueKind::ServerTimestamp => transform.set_to_server_value = Some("REQUEST_TIME".to_owned()),
+            FieldValueKind::Increment(n) => transform.increment = Some(serde_value_to_firebase_value(n)),
+            FieldValueKind::Maximum(n) => transform.maximum = Some(serde_value_to_firebase_value(n)),
+            FieldValueKind::Minimum(n) => transform.minimum = Some(serde_value_to_firebase_value(n)),
+            FieldValueKind::ArrayUnion(elements) => transform.append_missing_elements = Some(array(elements)),
+            FieldValueKind::ArrayRemove(elements) => transform.remove_all_from_array = Some(array(elements)),
+            FieldValueKind::Delete => return None,
+        }
+        Some(transform)
+    }
+}
+
+#[test]
+fn field_value_to_field_transform() {
+    let value = crate::firestore_serde::to_value(&FieldValue::array_union(vec![1, 2])).unwrap();
+    let transform = FieldValue::from_firebase_value(&value)
+        .unwrap()
+        .unwrap()
+        .to_field_transform("a.b".to_owned())
+        .unwrap();
+    assert_eq!(
+        serde_json::to_value(transform).unwrap(),
+        serde_json::json!({
+            "fieldPath": "a.b",
+            "appendMissingElements": {"values": [{"integerValue": "1"}, {"integerValue": "2"}]}
+        })
+    );
+
+    let value = crate::firestore_serde::to_value(&serde_json::json!({"a": 1})).unwrap();
+    assert!(FieldValue::from_firebase_value(&value).unwrap().is_none());
+    assert!(FieldValue::delete().to_field_transform("a".to_owned()).is_none());
+}
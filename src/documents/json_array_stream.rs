@@ -0,0 +1,124 @@
+//! Streaming endpoints like runQuery respond with a JSON array that is send in chunks.
+//! The parser in here extracts the array elements as soon as they are complete,
+//! so that a response does not need to be held in memory as a whole.
+
+use serde::de::DeserializeOwned;
+
+use crate::errors::{FirebaseError, Result};
+
+/// An incremental parser for a JSON array of objects. Feed data with [`JsonArrayParser::push`]
+/// and retrieve complete elements with [`JsonArrayParser::next_element`].
+#[derive(Default)]
+pub(crate) struct JsonArrayParser {
+    buffer: Vec<u8>,
+    /// Scan position within the buffer
+    pos: usize,
+    /// Nesting depth at the scan position. The array itself is depth 1.
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// Start of the current element within the buffer
+    element_start: Option<usize>,
+}
+
+impl JsonArrayParser {
+    /// Append received data
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the next complete element of the array, if any.
+    pub fn next_element<T: DeserializeOwned>(&mut self) -> Option<Result<T>> {
+        while self.pos < self.buffer.len() {
+            let c = self.buffer[self.pos];
+            self.pos += 1;
+
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if c == b'\\' {
+                    self.escaped = true;
+                } else if c == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+
+            match c {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => {
+                    if self.depth == 1 {
+                        self.element_start = Some(self.pos - 1);
+                    }
+                    self.depth += 1;
+                }
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if self.depth == 1 {
+                        if let Some(start) = self.element_start.take() {
+                            let result = serde_json::from_slice(&self.buffer[start..self.pos]).map_err(|e| {
+                                FirebaseError::SerdeVerbose {
+                                    doc: None,
+                                    input_doc: String::from_utf8_lossy(&self.buffer[start..self.pos]).to_string(),
+                                    ser: e,
+                                }
+                            });
+                            self.buffer.drain(..self.pos);
+                            self.pos = 0;
+                            return Some(result);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Nothing of value is kept in front of an unfinished element
+        if self.element_start.is_none() {
+            self.buffer.clear();
+            self.pos = 0;
+        }
+        None
+    }
+
+    /// Returns true if an element has been started but not yet completed
+    pub fn has_pending_element(&self) -> bool {
+        self.element_start.is_some()
+    }
+}
+
+#[test]
+fn json_array_parser_chunked() {
+    let input = br#"[{"a": "x}{\"", "b": [1, {"c": 2}]},
+        {"a": "y"}
+    ]"#;
+
+    // Feed the input in small chunks to cover elements split across chunks
+    let mut parser = JsonArrayParser::default();
+    let mut elements: Vec<serde_json::Value> = Vec::new();
+    for chunk in input.chunks(3) {
+        parser.push(chunk);
+        while let Some(element) = parser.next_element() {
+            elements.push(element.unwrap());
+        }
+    }
+    assert!(!parser.has_pending_element());
+    assert_eq!(
+        elements,
+        vec![
+            serde_json::json!({"a": "x}{\"", "b": [1, {"c": 2}]}),
+            serde_json::json!({"a": "y"})
+        ]
+    );
+}
+
+#[test]
+fn json_array_parser_incomplete() {
+    let mut parser = JsonArrayParser::default();
+    parser.push(br#"[{"a": {"b": 1}"#);
+    assert!(parser.next_element::<serde_json::Value>().is_none());
+    assert!(parser.has_pending_element());
+    parser.push(b"}]");
+    assert!(parser.next_element::<serde_json::Value>().unwrap().is_ok());
+    assert!(!parser.has_pending_element());
+}
@@ -1,17 +1,14 @@
 use super::*;
-use bytes::Bytes;
 use core::pin::Pin;
-use futures::{
-    stream::{self, Stream},
-    task::{Context, Poll},
-    Future,
-};
+use futures::stream::{self, BoxStream, Stream, StreamExt};
 use std::boxed::Box;
+use std::collections::VecDeque;
 
 /// List all documents of a given collection.
 ///
 /// Please note that this API acts as an iterator of same-like documents.
 /// This type is not suitable if you want to list documents of different types.
+/// If the collection path is invalid, the stream only returns an error.
 ///
 /// Example:
 /// ```no_run
@@ -49,84 +46,212 @@ where
     for<'b> T: Deserialize<'b> + 'static,
     AUTH: FirebaseAuthBearer + Clone + Send + Sync + 'static,
 {
-    let auth = auth.clone();
     let collection_id = collection_id.into();
+    let params = ListOptions::default().params();
+    Box::pin(list_documents(auth, collection_id, params).map(|result| {
+        let doc = result?;
+        let pod = document_to_pod(&doc, None)?;
+        Ok((
+            pod,
+            dto::Document {
+                update_time: doc.update_time,
+                create_time: doc.create_time,
+                name: doc.name,
+                fields: None,
+            },
+        ))
+    }))
+}
 
-    Box::pin(stream::unfold(
-        ListInner {
-            url: firebase_url(auth.project_id(), &collection_id),
-            auth,
-            next_page_token: None,
-            documents: vec![],
-            current: 0,
-            done: false,
-            collection_id: collection_id.to_string(),
-        },
-        |this| async move {
-            let mut this = this.clone();
-            if this.done {
-                return None;
-            }
+/// List options for [`list_with_options`]
+#[derive(Default, Clone, Debug)]
+pub struct ListOptions {
+    /// The maximum number of documents that are requested per page. The server default is used if not set.
+    pub page_size: Option<i32>,
+    /// Order the documents by the given fields, for example `vec![("title".to_owned(), dto::Direction::ASCENDING)]`.
+    /// Documents are ordered by their name by default.
+    pub order_by: Vec<(String, dto::Direction)>,
+    /// If set, only the given field paths are returned, for example `vec!["title".to_owned()]`.
+    /// Use this to deserialize into a smaller projection type.
+    pub mask: Option<Vec<String>>,
+    /// Also list documents that do not exist, but have subcollections.
+    /// Those are returned as [`ListItem::Missing`].
+    pub show_missing: bool,
+    /// If set, documents are listed as they were at the given time. This must be a time within the past hour.
+    /// All pages are read from the same consistent snapshot.
+    pub read_time: Option<chrono::DateTime<chrono::Utc>>,
+}
 
-            if this.documents.len() <= this.current {
-                let url = match &this.next_page_token {
-                    Some(next_page_token) => format!("{}pageToken={}", this.url, next_page_token),
-                    None => this.url.clone(),
-                };
-
-                let result = get_new_data(&this.collection_id, &url, &this.auth).await;
-                match result {
-                    Err(e) => {
-                        this.done = true;
-                        return Some((Err(e), this));
-                    }
-                    Ok(v) => match v.documents {
-                        None => return None,
-                        Some(documents) => {
-                            this.documents = documents;
-                            this.current = 0;
-                            this.next_page_token = v.next_page_token;
-                        }
-                    },
-                }
-            }
+impl ListOptions {
+    /// Returns the query parameters of these options
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = mask_params(&self.mask);
+        if let Some(page_size) = self.page_size {
+            params.push(("pageSize", page_size.to_string()));
+        }
+        if !self.order_by.is_empty() {
+            let order_by: Vec<String> = self
+                .order_by
+                .iter()
+                .map(|(field, direction)| match direction {
+                    dto::Direction::DESCENDING => format!("{} desc", field),
+                    _ => field.clone(),
+                })
+                .collect();
+            params.push(("orderBy", order_by.join(", ")));
+        }
+        if self.show_missing {
+            params.push(("showMissing", "true".to_owned()));
+        }
+        if let Some(read_time) = self.read_time.as_ref() {
+            params.push(("readTime", format_timestamp(read_time)));
+        }
+        params
+    }
+}
+
+/// A listed document, see [`list_with_options`]
+#[derive(Debug)]
+pub enum ListItem<T> {
+    /// An existing document and its metadata
+    Document(T, DocumentMeta),
+    /// A document that does not exist, but has subcollections. Only the name of the metadata is set.
+    /// This is only returned with [`ListOptions::show_missing`].
+    Missing(DocumentMeta),
+}
+
+impl<T> ListItem<T> {
+    /// Returns the document and its metadata, or None for a missing document
+    pub fn document(self) -> Option<(T, DocumentMeta)> {
+        match self {
+            ListItem::Document(doc, meta) => Some((doc, meta)),
+            ListItem::Missing(_) => None,
+        }
+    }
+}
+
+/// List all documents of a given collection with the given options. See [`list`].
+///
+/// Example:
+/// ```no_run
+/// # use futures::StreamExt;
+/// use serde::Deserialize;
+/// #[derive(Debug, Deserialize)]
+/// struct TitleOnly { title: String, }
+///
+/// use firestore_db_and_auth::{documents, documents::ListItem, dto};
+/// # tokio_test::block_on(async {
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// let options = documents::ListOptions {
+///     mask: Some(vec!["title".to_owned()]),
+///     order_by: vec![("title".to_owned(), dto::Direction::DESCENDING)],
+///     page_size: Some(50),
+///     show_missing: true,
+///     ..Default::default()
+/// };
+/// let mut stream = documents::list_with_options(&session, "books", options);
+/// while let Some(item) = stream.next().await {
+///     match item.unwrap() {
+///         ListItem::Document(doc, metadata) => {
+///             let doc: TitleOnly = doc;
+///             println!("{}: {}", metadata.name, doc.title);
+///         }
+///         ListItem::Missing(metadata) => println!("{} only has subcollections", metadata.name),
+///     }
+/// }
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'collection_id' The document path / collection; For example "my_collection" or "a/nested/collection"
+/// * 'options' List options like the page size, ordering or a field mask
+pub fn list_with_options<T, AUTH>(
+    auth: &AUTH,
+    collection_id: impl Into<String>,
+    options: ListOptions,
+) -> BoxStream<'static, Result<ListItem<T>>>
+where
+    for<'b> T: Deserialize<'b> + 'static,
+    AUTH: FirebaseAuthBearer + Clone + Send + Sync + 'static,
+{
+    Box::pin(list_documents(auth, collection_id.into(), options.params()).map(|result| list_item(result?)))
+}
 
-            let doc = this.documents.get(this.current).unwrap().clone();
+fn list_item<T>(document: dto::Document) -> Result<ListItem<T>>
+where
+    for<'b> T: Deserialize<'b>,
+{
+    let meta = DocumentMeta::from_document(&document)?;
+    // Missing documents have neither fields nor a create or update time
+    if document.create_time.is_none() && document.update_time.is_none() {
+        return Ok(ListItem::Missing(meta));
+    }
+    Ok(ListItem::Document(document_to_pod(&document, None)?, meta))
+}
 
-            this.current += 1;
+/// Lists the documents of the given collection page by page with the given query parameters
+pub(crate) fn list_documents<AUTH>(
+    auth: &AUTH,
+    collection_id: String,
+    params: Vec<(&'static str, String)>,
+) -> BoxStream<'static, Result<dto::Document>>
+where
+    AUTH: FirebaseAuthBearer + Clone + Send + Sync + 'static,
+{
+    let collection = match CollectionRef::new(&collection_id) {
+        Ok(collection) => collection,
+        Err(e) => return error_stream(e),
+    };
+    let state = ListInner {
+        url: firebase_url(auth, collection.as_ref()),
+        auth: auth.clone(),
+        params,
+        collection_id,
+        documents: VecDeque::new(),
+        next_page_token: None,
+        first_page: true,
+    };
 
-            if this.documents.len() <= this.current && this.next_page_token.is_none() {
-                this.done = true;
+    Box::pin(stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        loop {
+            if let Some(document) = state.documents.pop_front() {
+                return Some((Ok(document), Some(state)));
+            }
+            if !state.first_page && state.next_page_token.is_none() {
+                return None;
             }
+            state.first_page = false;
 
-            let result = document_to_pod(&doc, None);
-            match result {
-                Err(e) => Some((Err(e), this)),
-                Ok(pod) => Some((
-                    Ok((
-                        pod,
-                        dto::Document {
-                            update_time: doc.update_time.clone(),
-                            create_time: doc.create_time.clone(),
-                            name: doc.name.clone(),
-                            fields: None,
-                        },
-                    )),
-                    this,
-                )),
+            let mut params = state.params.clone();
+            if let Some(page_token) = state.next_page_token.take() {
+                params.push(("pageToken", page_token));
             }
-        },
-    ))
+            match get_new_data(&state.collection_id, &state.url, &params, &state.auth).await {
+                Ok(response) => {
+                    state.documents = response.documents.unwrap_or_default().into();
+                    state.next_page_token = response.next_page_token.filter(|token| !token.is_empty());
+                }
+                Err(e) => return Some((Err(e), None)),
+            }
+        }
+    }))
 }
 
 async fn get_new_data<'a>(
     collection_id: &str,
     url: &str,
+    params: &[(&str, String)],
     auth: &'a impl FirebaseAuthBearer,
 ) -> Result<dto::ListDocumentsResponse> {
     let resp = auth
         .client()
         .get(url)
+        .query(params)
         .bearer_auth(auth.access_token().await)
         .send()
         .await?;
@@ -137,13 +262,52 @@ async fn get_new_data<'a>(
     Ok(json)
 }
 
-#[derive(Clone)]
 struct ListInner<AUTH> {
     auth: AUTH,
-    next_page_token: Option<String>,
-    documents: Vec<dto::Document>,
-    current: usize,
-    done: bool,
     url: String,
+    params: Vec<(&'static str, String)>,
     collection_id: String,
+    documents: VecDeque<dto::Document>,
+    next_page_token: Option<String>,
+    first_page: bool,
+}
+
+#[test]
+fn list_options_params() {
+    let options = ListOptions {
+        page_size: Some(10),
+        order_by: vec![
+            ("a".to_owned(), dto::Direction::ASCENDING),
+            ("b".to_owned(), dto::Direction::DESCENDING),
+        ],
+        show_missing: true,
+        read_time: Some("2020-04-28T14:52:51.25Z".parse().unwrap()),
+        ..Default::default()
+    };
+    assert_eq!(
+        options.params(),
+        [
+            ("pageSize", "10".to_owned()),
+            ("orderBy", "a, b desc".to_owned()),
+            ("showMissing", "true".to_owned()),
+            ("readTime", "2020-04-28T14:52:51.250000Z".to_owned())
+        ]
+    );
+
+    let missing = dto::Document {
+        name: "projects/p/databases/(default)/documents/tests/a".to_owned(),
+        ..Default::default()
+    };
+    let item: ListItem<serde_json::Value> = list_item(missing).unwrap();
+    assert!(matches!(item, ListItem::Missing(meta) if meta.name.ends_with("tests/a")));
+}
+
+#[test]
+fn list_invalid_collection() {
+    use futures::StreamExt;
+    let auth = tests::TestAuth::new();
+    let mut stream = list_with_options::<serde_json::Value, _>(&auth, "tests/a", ListOptions::default());
+    let result = futures::executor::block_on(stream.next()).unwrap();
+    assert!(matches!(result, Err(FirebaseError::Generic(_))));
+    assert!(futures::executor::block_on(stream.next()).is_none());
 }
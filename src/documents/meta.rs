@@ -0,0 +1,93 @@
+use super::*;
+use chrono::{DateTime, Utc};
+
+/// Metadata of a Firestore document. This is returned alongside the deserialized document data.
+#[derive(Debug, Clone, Default)]
+pub struct DocumentMeta {
+    /// The absolute document path, for example "projects/{PROJECT_ID}/databases/(default)/documents/my_collection/document_id".
+    /// Use [`DocumentRef::from_name`] to parse this into a validated document reference.
+    pub name: String,
+    /// The document id, for example "document_id"
+    pub id: String,
+    /// The relative document path, for example "my_collection/document_id"
+    pub path: String,
+    /// The time at which the document was created
+    pub create_time: Option<DateTime<Utc>>,
+    /// The time at which the document was last changed
+    pub update_time: Option<DateTime<Utc>>,
+    /// The time at which the document was read
+    pub read_time: Option<DateTime<Utc>>,
+    /// Query results only: The number of results that have been skipped due to an offset
+    /// between the previous result and this one. Results skipped after the last document are not reported.
+    pub skipped_results: i32,
+    /// Query results only: The transaction that was started as part of the query, if any
+    pub transaction: Option<String>,
+}
+
+impl DocumentMeta {
+    /// Creates metadata with the given absolute document name and the id and relative path derived from it
+    pub fn from_name(name: impl Into<String>) -> DocumentMeta {
+        let name = name.into();
+        let path = abs_to_rel(&name).to_owned();
+        DocumentMeta {
+            id: path.rsplit('/').next().unwrap_or_default().to_owned(),
+            path,
+            name,
+            ..Default::default()
+        }
+    }
+
+    /// Extracts the metadata of the given document
+    pub fn from_document(document: &dto::Document) -> Result<DocumentMeta> {
+        Ok(DocumentMeta {
+            create_time: parse_timestamp(
+                &document.create_time,
+                "Failed to parse rfc3339 date from 'create_time' field",
+            )?,
+            update_time: parse_timestamp(
+                &document.update_time,
+                "Failed to parse rfc3339 date from 'update_time' field",
+            )?,
+            ..DocumentMeta::from_name(document.name.clone())
+        })
+    }
+}
+
+/// Parses a rfc3339 timestamp as used by the Firestore REST API.
+/// The given error message is returned if parsing fails.
+pub(crate) fn parse_timestamp(value: &Option<String>, error: &'static str) -> Result<Option<DateTime<Utc>>> {
+    match value {
+        Some(value) => Ok(Some(
+            DateTime::parse_from_rfc3339(value)
+                .map_err(|_| FirebaseError::Generic(error))?
+                .with_timezone(&Utc),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Formats a timestamp as rfc3339 string as expected by the Firestore REST API
+pub(crate) fn format_timestamp(value: &DateTime<Utc>) -> String {
+    value.to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+}
+
+#[test]
+fn document_meta_from_document() {
+    let meta = DocumentMeta::from_document(&dto::Document {
+        name: "projects/p/databases/(default)/documents/tests/a".to_owned(),
+        create_time: Some("2020-04-28T14:52:51.250511Z".to_owned()),
+        update_time: None,
+        fields: None,
+    })
+    .unwrap();
+    assert_eq!(meta.id, "a");
+    assert_eq!(meta.path, "tests/a");
+    assert_eq!(meta.create_time.unwrap().timestamp_subsec_micros(), 250511);
+    assert!(meta.update_time.is_none());
+
+    let invalid = dto::Document {
+        create_time: Some("yesterday".to_owned()),
+        ..Default::default()
+    };
+    assert!(DocumentMeta::from_document(&invalid).is_err());
+}
@@ -4,23 +4,55 @@
 //! Please check the root page of this documentation for examples.
 #![allow(unused_imports, dead_code)]
 use super::dto;
-use super::errors::{extract_google_api_error, extract_google_api_error_async, FirebaseError, Result};
-use super::firebase_rest_to_rust::{document_to_pod, pod_to_document};
+use super::emulator::firestore_url;
+use super::errors::{
+    extract_google_api_error, extract_google_api_error_async, extract_google_write_error_async, FirebaseError, Result,
+};
+use super::firebase_rest_to_rust::{document_to_pod, pod_to_document, pod_to_document_with_transforms};
 use super::FirebaseAuthBearer;
 
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
+mod aggregation;
+mod batch;
+mod batch_get;
+mod bulk_writer;
+mod collection_ids;
 mod delete;
+mod dynamic;
+mod field_path;
+mod field_value;
+mod json_array_stream;
 mod list;
+mod meta;
+mod path;
 mod query;
+mod query_builder;
 mod read;
+mod recursive_delete;
+mod transaction;
+mod values;
 mod write;
 
+pub use aggregation::*;
+pub use batch::*;
+pub use batch_get::*;
+pub use bulk_writer::*;
+pub use collection_ids::*;
 pub use delete::*;
+pub use dynamic::*;
+pub use field_path::*;
+pub use field_value::*;
 pub use list::*;
+pub use meta::*;
+pub use path::*;
 pub use query::*;
+pub use query_builder::*;
 pub use read::*;
+pub use recursive_delete::*;
+pub use transaction::*;
+pub use values::*;
 pub use write::*;
 
 /// An [`Iterator`] implementation that provides a join method
@@ -52,40 +84,112 @@ pub trait JoinableIterator: Iterator {
 impl<'a, VALUE> JoinableIterator for std::collections::hash_map::Keys<'a, String, VALUE> {}
 
 #[inline]
-fn firebase_url_query(v1: &str) -> String {
+fn firebase_url_query(auth: &impl FirebaseAuthBearer, v2: &str) -> String {
+    firebase_url_parent_action(auth, v2, "runQuery")
+}
+
+#[inline]
+fn firebase_url_parent_action(auth: &impl FirebaseAuthBearer, v2: &str, action: &str) -> String {
+    match v2.is_empty() {
+        true => firebase_url_action(auth, action),
+        false => format!(
+            "{}/{}/documents/{}:{}",
+            firestore_url(auth.emulator()),
+            firebase_database_name(auth),
+            v2,
+            action
+        ),
+    }
+}
+
+#[inline]
+fn firebase_url_action(auth: &impl FirebaseAuthBearer, v2: &str) -> String {
     format!(
-        "https://firestore.googleapis.com/v1/projects/{}/databases/(default)/documents:runQuery",
-        v1
+        "{}/{}/documents:{}",
+        firestore_url(auth.emulator()),
+        firebase_database_name(auth),
+        v2
     )
 }
 
 #[inline]
-fn firebase_url_base(v1: &str) -> String {
-    format!("https://firestore.googleapis.com/v1/{}", v1)
+fn firebase_url_base(auth: &impl FirebaseAuthBearer, v1: &str) -> String {
+    format!("{}/{}", firestore_url(auth.emulator()), v1)
 }
 
 #[inline]
-fn firebase_url_extended(v1: &str, v2: &str, v3: &str) -> String {
+fn firebase_url_extended(auth: &impl FirebaseAuthBearer, v2: &str, v3: &str) -> String {
     format!(
-        "https://firestore.googleapis.com/v1/projects/{}/databases/(default)/documents/{}/{}",
-        v1, v2, v3
+        "{}/{}/documents/{}/{}",
+        firestore_url(auth.emulator()),
+        firebase_database_name(auth),
+        v2,
+        v3
     )
 }
 
 #[inline]
-fn firebase_url(v1: &str, v2: &str) -> String {
+fn firebase_url(auth: &impl FirebaseAuthBearer, v2: &str) -> String {
     format!(
-        "https://firestore.googleapis.com/v1/projects/{}/databases/(default)/documents/{}?",
-        v1, v2
+        "{}/{}/documents/{}?",
+        firestore_url(auth.emulator()),
+        firebase_database_name(auth),
+        v2
     )
 }
 
+#[inline]
+fn firebase_document_name(auth: &impl FirebaseAuthBearer, v2: &str) -> String {
+    format!("{}/documents/{}", firebase_database_name(auth), v2)
+}
+
+/// The resource name of the database of the given session, like "projects/{PROJECT_ID}/databases/(default)"
+#[inline]
+fn firebase_database_name(auth: &impl FirebaseAuthBearer) -> String {
+    format!("projects/{}/databases/{}", auth.project_id(), auth.database_id())
+}
+
+/// The delay before retrying an operation for the given attempt (starting with 1).
+/// The delay grows exponentially up to a few seconds and contains a random jitter of up to half of it,
+/// so that clients that failed at the same time do not retry at the same time.
+fn retry_delay(attempt: u32) -> std::time::Duration {
+    use ring::rand::SecureRandom;
+
+    let base = 250u64 * 2u64.pow(attempt.clamp(1, 6) - 1);
+    let mut bytes = [0u8; 8];
+    // Without a random source, the delay is still exponential, just without the jitter
+    let random = match ring::rand::SystemRandom::new().fill(&mut bytes) {
+        Ok(()) => u64::from_le_bytes(bytes),
+        Err(_) => 0,
+    };
+    std::time::Duration::from_millis(base + random % (base / 2 + 1))
+}
+
+/// Returns true for errors that are likely to disappear if the operation is retried,
+/// like network errors or an overloaded or unavailable server.
+fn is_transient_error(error: &FirebaseError) -> bool {
+    match error {
+        FirebaseError::Request(_) | FirebaseError::Aborted(_, _) => true,
+        FirebaseError::APIError(code, _, _) => matches!(code, 429 | 500 | 502 | 503 | 504),
+        FirebaseError::UnexpectedResponse(_, status, _, _) => {
+            status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        _ => false,
+    }
+}
+
+/// A stream that only returns the given error, for stream functions with invalid arguments
+fn error_stream<T: Send + 'static>(error: FirebaseError) -> futures::stream::BoxStream<'static, Result<T>> {
+    Box::pin(futures::stream::once(async move { Err(error) }))
+}
+
 /// Converts an absolute path like "projects/{PROJECT_ID}/databases/(default)/documents/my_collection/document_id"
 /// into a relative document path like "my_collection/document_id"
 ///
-/// This is usually used to get a suitable path for [`delete`].
+/// This is usually used to get a suitable path for [`delete`]. Paths that are not absolute are returned as they are.
+/// Use [`DocumentRef::from_name`] to parse and validate an absolute path instead.
 pub fn abs_to_rel(path: &str) -> &str {
-    &path[path.find("(default)").unwrap() + 20..]
+    relative_path(path).unwrap_or(path)
 }
 
 #[test]
@@ -94,4 +198,59 @@ fn abs_to_rel_test() {
         abs_to_rel("projects/{PROJECT_ID}/databases/(default)/documents/my_collection/document_id"),
         "my_collection/document_id"
     );
+    assert_eq!(
+        abs_to_rel("projects/p/databases/named/documents/my_collection/document_id"),
+        "my_collection/document_id"
+    );
+    assert_eq!(abs_to_rel("my_collection/document_id"), "my_collection/document_id");
+}
+
+#[test]
+fn retry_delay_jitter() {
+    for attempt in 1..10 {
+        let base = 250 * 2u128.pow(attempt.clamp(1, 6) - 1);
+        let delays: Vec<u128> = (0..20).map(|_| retry_delay(attempt).as_millis()).collect();
+        assert!(delays.iter().all(|delay| (base..=base + base / 2).contains(delay)));
+        // The jitter does not depend on the time of the call
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// An authentication stub for tests that do not perform requests
+    #[derive(Clone)]
+    pub(crate) struct TestAuth(pub reqwest::Client, pub &'static str);
+
+    impl TestAuth {
+        /// A stub for the default database of the project "project"
+        pub(crate) fn new() -> Self {
+            TestAuth(reqwest::Client::new(), "(default)")
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl FirebaseAuthBearer for TestAuth {
+        fn project_id(&self) -> &str {
+            "project"
+        }
+
+        fn database_id(&self) -> &str {
+            self.1
+        }
+
+        async fn access_token(&self) -> String {
+            String::new()
+        }
+
+        async fn access_token_unchecked(&self) -> String {
+            String::new()
+        }
+
+        fn client(&self) -> &reqwest::Client {
+            &self.0
+        }
+    }
 }
@@ -0,0 +1,250 @@
+use super::*;
+use std::fmt;
+use std::str::FromStr;
+
+///
+/// A validated, relative path of a collection, like "users" or "users/abc/posts".
+///
+/// Use it wherever a collection path is expected, for example with [`read`], [`write`] or [`list`].
+/// Create document references with [`CollectionRef::doc`].
+///
+/// Example:
+/// ```
+/// use firestore_db_and_auth::documents::CollectionRef;
+///
+/// let posts = CollectionRef::new("users")?.doc("abc")?.collection("posts")?;
+/// assert_eq!(posts.as_ref(), "users/abc/posts");
+/// assert_eq!(posts.parent().unwrap().id(), "abc");
+/// assert!(CollectionRef::new("users/abc").is_err());
+/// # Ok::<(), firestore_db_and_auth::errors::FirebaseError>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CollectionRef(String);
+
+///
+/// A validated, relative path of a document, like "users/abc".
+///
+/// Use it wherever a document path is expected, for example with [`delete`] or [`batch_get`].
+/// Create references to subcollections with [`DocumentRef::collection`].
+///
+/// Example:
+/// ```
+/// use firestore_db_and_auth::documents::DocumentRef;
+///
+/// let doc = DocumentRef::from_name("projects/p/databases/(default)/documents/users/abc")?;
+/// assert_eq!(doc.as_ref(), "users/abc");
+/// assert_eq!(doc.parent().as_ref(), "users");
+/// assert!(DocumentRef::new("users//abc").is_err());
+/// # Ok::<(), firestore_db_and_auth::errors::FirebaseError>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DocumentRef(String);
+
+impl CollectionRef {
+    /// Validates the given collection path. Leading and trailing slashes are ignored.
+    pub fn new(path: impl AsRef<str>) -> Result<Self> {
+        let path = path.as_ref().trim_matches('/');
+        validate_path(path, false)?;
+        Ok(CollectionRef(path.to_owned()))
+    }
+
+    /// Parses a full resource name like "projects/{PROJECT_ID}/databases/(default)/documents/users"
+    pub fn from_name(name: &str) -> Result<Self> {
+        Self::new(relative_path(name)?)
+    }
+
+    /// The collection id, which is the last segment of the path
+    pub fn id(&self) -> &str {
+        last_segment(&self.0)
+    }
+
+    /// The document this collection belongs to, or None for a root collection
+    pub fn parent(&self) -> Option<DocumentRef> {
+        self.0
+            .rsplit_once('/')
+            .map(|(parent, _)| DocumentRef(parent.to_owned()))
+    }
+
+    /// A reference to the document with the given id within this collection
+    pub fn doc(&self, document_id: impl AsRef<str>) -> Result<DocumentRef> {
+        let document_id = document_id.as_ref();
+        validate_segment(document_id)?;
+        Ok(DocumentRef(format!("{}/{}", self.0, document_id)))
+    }
+}
+
+impl DocumentRef {
+    /// Validates the given document path. Leading and trailing slashes are ignored.
+    pub fn new(path: impl AsRef<str>) -> Result<Self> {
+        let path = path.as_ref().trim_matches('/');
+        validate_path(path, true)?;
+        Ok(DocumentRef(path.to_owned()))
+    }
+
+    /// Parses a full resource name like "projects/{PROJECT_ID}/databases/(default)/documents/users/abc",
+    /// as found in [`DocumentMeta::name`]
+    pub fn from_name(name: &str) -> Result<Self> {
+        Self::new(relative_path(name)?)
+    }
+
+    /// The document id, which is the last segment of the path
+    pub fn id(&self) -> &str {
+        last_segment(&self.0)
+    }
+
+    /// The collection this document belongs to
+    pub fn parent(&self) -> CollectionRef {
+        let (parent, _) = self.0.rsplit_once('/').unwrap_or_default();
+        CollectionRef(parent.to_owned())
+    }
+
+    /// A reference to the subcollection with the given id of this document
+    pub fn collection(&self, collection_id: impl AsRef<str>) -> Result<CollectionRef> {
+        let collection_id = collection_id.as_ref();
+        validate_segment(collection_id)?;
+        Ok(CollectionRef(format!("{}/{}", self.0, collection_id)))
+    }
+
+    /// The full resource name of this document within the project and database of the given session
+    pub fn name(&self, auth: &impl FirebaseAuthBearer) -> String {
+        firebase_document_name(auth, &self.0)
+    }
+}
+
+macro_rules! path_type_impls {
+    ($type:ident) => {
+        impl AsRef<str> for $type {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $type {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl FromStr for $type {
+            type Err = FirebaseError;
+
+            fn from_str(path: &str) -> Result<Self> {
+                Self::new(path)
+            }
+        }
+
+        impl From<$type> for String {
+            fn from(path: $type) -> String {
+                path.0
+            }
+        }
+
+        impl From<&$type> for String {
+            fn from(path: &$type) -> String {
+                path.0.clone()
+            }
+        }
+    };
+}
+
+path_type_impls!(CollectionRef);
+path_type_impls!(DocumentRef);
+
+fn last_segment(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or_default()
+}
+
+/// Returns the relative path of a full resource name like "projects/{PROJECT_ID}/databases/{DATABASE_ID}/documents/a/b"
+pub(crate) fn relative_path(name: &str) -> Result<&str> {
+    let mut segments = name.splitn(6, '/');
+    match (
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+    ) {
+        (Some("projects"), Some(project), Some("databases"), Some(database), Some("documents"))
+            if !project.is_empty() && !database.is_empty() =>
+        {
+            Ok(segments.next().unwrap_or_default())
+        }
+        _ => Err(FirebaseError::Generic("Invalid Firestore resource name")),
+    }
+}
+
+/// Validates a relative collection or document path
+fn validate_path(path: &str, document: bool) -> Result<()> {
+    if path.is_empty() {
+        return Err(FirebaseError::Generic("Empty collection or document path"));
+    }
+    let mut segments = 0;
+    for segment in path.split('/') {
+        validate_segment(segment)?;
+        segments += 1;
+    }
+    match (document, segments % 2 == 0) {
+        (true, false) => Err(FirebaseError::Generic(
+            "A document path must have an even number of segments",
+        )),
+        (false, true) => Err(FirebaseError::Generic(
+            "A collection path must have an odd number of segments",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Validates a single collection or document id
+fn validate_segment(segment: &str) -> Result<()> {
+    if segment.is_empty() {
+        return Err(FirebaseError::Generic("Empty segment in collection or document path"));
+    }
+    if segment.contains('/') {
+        return Err(FirebaseError::Generic(
+            "A collection or document id must not contain a slash",
+        ));
+    }
+    if segment == "." || segment == ".." {
+        return Err(FirebaseError::Generic(
+            "A collection or document id must not be '.' or '..'",
+        ));
+    }
+    if segment.len() >= 4 && segment.starts_with("__") && segment.ends_with("__") {
+        return Err(FirebaseError::Generic(
+            "Collection and document ids of the form __.*__ are reserved",
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn path_validation() {
+    assert!(CollectionRef::new("users").is_ok());
+    assert!(CollectionRef::new("/users/abc/posts/").is_ok());
+    assert!(DocumentRef::new("users/abc").is_ok());
+
+    assert!(CollectionRef::new("").is_err());
+    assert!(CollectionRef::new("users/abc").is_err());
+    assert!(DocumentRef::new("users").is_err());
+    assert!(DocumentRef::new("one/two//three/my-document").is_err());
+    assert!(DocumentRef::new("users/..").is_err());
+    assert!(DocumentRef::new("users/__id__").is_err());
+    assert!(CollectionRef::new("users").unwrap().doc("a/b").is_err());
+    assert!(DocumentRef::new("users/_a_").is_ok());
+}
+
+#[test]
+fn path_navigation() {
+    let doc = DocumentRef::from_name("projects/p/databases/(default)/documents/users/abc/posts/1").unwrap();
+    assert_eq!(doc.id(), "1");
+    assert_eq!(doc.parent().as_ref(), "users/abc/posts");
+    assert_eq!(doc.parent().parent().unwrap().as_ref(), "users/abc");
+    assert!(CollectionRef::new("users").unwrap().parent().is_none());
+    assert_eq!(
+        doc.name(&tests::TestAuth::new()),
+        "projects/project/databases/(default)/documents/users/abc/posts/1"
+    );
+
+    assert!(DocumentRef::from_name("users/abc").is_err());
+    assert!(DocumentRef::from_name("projects/p/databases/(default)/documents").is_err());
+}
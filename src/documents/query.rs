@@ -1,4 +1,6 @@
+use super::json_array_stream::JsonArrayParser;
 use super::*;
+use futures::stream::{self, BoxStream};
 use std::vec::IntoIter;
 
 ///
@@ -29,7 +31,7 @@ use std::vec::IntoIter;
 ///
 /// ## Arguments
 /// * 'auth' The authentication token
-/// * 'collectionid' The collection id; "my_collection" or "a/nested/collection"
+/// * 'collection_id' The collection id; "my_collection" or "a/nested/collection"
 /// * 'value' The query / filter value. For example "car".
 /// * 'operator' The query operator. For example "EQUAL".
 /// * 'field' The query / filter field. For example "type".
@@ -40,8 +42,8 @@ pub async fn query(
     operator: dto::FieldOperator,
     field: &str,
 ) -> Result<Query> {
-    let url = firebase_url_query(auth.project_id());
     let value = crate::firebase_rest_to_rust::serde_value_to_firebase_value(&value);
+    let (parent, collection) = split_collection_path(collection_id);
 
     let query_request = dto::RunQueryRequest {
         structured_query: Some(dto::StructuredQuery {
@@ -57,7 +59,7 @@ pub async fn query(
                 ..Default::default()
             }),
             from: Some(vec![dto::CollectionSelector {
-                collection_id: Some(collection_id.to_owned()),
+                collection_id: Some(collection.to_owned()),
                 ..Default::default()
             }]),
             ..Default::default()
@@ -65,22 +67,255 @@ pub async fn query(
         ..Default::default()
     };
 
+    let json = run_query_request(auth, &query_request, parent, collection_id).await?;
+    Ok(Query(json.into_iter()))
+}
+
+///
+/// Executes a structured query, build with a [`QueryBuilder`].
+///
+/// In contrast to [`query`], the returned documents contain all fields.
+/// Use [`crate::firebase_rest_to_rust::document_to_pod`] to convert a document into your own data type.
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'query' The query
+pub async fn run_query(auth: &impl FirebaseAuthBearer, query: &QueryBuilder) -> Result<Query> {
+    let query_request = query.query_request();
+
+    let json = run_query_request(auth, &query_request, query.parent_path(), query.collection_id()).await?;
+    Ok(Query(json.into_iter()))
+}
+
+///
+/// Executes a structured query, build with a [`QueryBuilder`], and returns all matching documents
+/// deserialized and together with their metadata, like the document id and path and the create and update time.
+///
+/// All results are held in memory. Use [`query_stream`] for large result sets.
+/// Like with [`query_stream`], skipped results after the last returned document are not reported.
+///
+/// Example:
+/// ```no_run
+/// use serde::Deserialize;
+/// #[derive(Debug, Deserialize)]
+/// struct DemoDTO { a_string: String, an_int: u32, }
+///
+/// use firestore_db_and_auth::{documents, documents::QueryBuilder};
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// let results: Vec<(DemoDTO, _)> = documents::query_with_meta(&session, &QueryBuilder::new("tests")).await.unwrap();
+/// for (doc, meta) in results {
+///     println!("{} (updated {:?}): {:?}", meta.id, meta.update_time, doc);
+/// }
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'query' The query
+pub async fn query_with_meta<T>(auth: &impl FirebaseAuthBearer, query: &QueryBuilder) -> Result<Vec<(T, DocumentMeta)>>
+where
+    for<'b> T: Deserialize<'b>,
+{
+    let responses = run_query_request(auth, &query.query_request(), query.parent_path(), query.collection_id()).await?;
+    query_responses_to_pods(responses)
+}
+
+///
+/// Executes a structured query, build with a [`QueryBuilder`], and returns the matching documents
+/// as a stream of deserialized documents and their metadata.
+///
+/// The response is parsed while it arrives, so large result sets are never held in memory as a whole.
+/// Besides the document name, create and update time, the metadata contains the read time,
+/// the number of results skipped due to an offset and the transaction id, if any.
+/// Results that are skipped after the last returned document, for example if the offset skips all results,
+/// are not reported, because there is no document to attach them to.
+///
+/// Please note that this API acts as a stream of same-like documents.
+///
+/// Example:
+/// ```no_run
+/// # use futures::StreamExt;
+/// use serde::{Serialize, Deserialize};
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct DemoDTO { a_string: String, an_int: u32, }
+///
+/// use firestore_db_and_auth::{documents, documents::{QueryBuilder, QueryFilter}, dto};
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// let query = QueryBuilder::new("tests").filter(QueryFilter::field("an_int", dto::FieldOperator::GREATER_THAN, 10.into()));
+/// let mut stream = documents::query_stream(&session, &query);
+/// while let Some(result) = stream.next().await {
+///     let (doc, metadata): (DemoDTO, _) = result.unwrap();
+///     println!("{}: {:?}", metadata.name, doc);
+/// }
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'query' The query
+pub fn query_stream<T, AUTH>(auth: &AUTH, query: &QueryBuilder) -> BoxStream<'static, Result<(T, DocumentMeta)>>
+where
+    for<'b> T: Deserialize<'b> + 'static,
+    AUTH: FirebaseAuthBearer + Clone + Send + Sync + 'static,
+{
+    let query_request = query.query_request();
+
+    Box::pin(stream::unfold(
+        QueryStreamState::Request(
+            auth.clone(),
+            Box::new(query_request),
+            query.parent_path().to_owned(),
+            query.collection_id().to_owned(),
+        ),
+        |state| async move {
+            let mut inner = match state {
+                QueryStreamState::Request(auth, query_request, parent, context) => {
+                    match send_query_request(&auth, &query_request, &parent, &context).await {
+                        Ok(response) => Box::new(QueryStreamInner {
+                            response,
+                            parser: JsonArrayParser::default(),
+                            skipped_results: 0,
+                            transaction: None,
+                        }),
+                        Err(e) => return Some((Err(e), QueryStreamState::Done)),
+                    }
+                }
+                QueryStreamState::Streaming(inner) => inner,
+                QueryStreamState::Done => return None,
+            };
+
+            loop {
+                match inner.parser.next_element::<dto::RunQueryResponse>() {
+                    Some(Err(e)) => return Some((Err(e), QueryStreamState::Done)),
+                    Some(Ok(response)) => {
+                        inner.skipped_results += response.skipped_results.unwrap_or_default();
+                        if response.transaction.is_some() {
+                            inner.transaction = response.transaction.clone();
+                        }
+                        if let Some(document) = response.document.as_ref() {
+                            let result =
+                                query_response_to_pod(&response, document, inner.skipped_results, &inner.transaction);
+                            inner.skipped_results = 0;
+                            return Some((result, QueryStreamState::Streaming(inner)));
+                        }
+                    }
+                    None => match inner.response.chunk().await {
+                        Ok(Some(chunk)) => inner.parser.push(&chunk),
+                        Ok(None) if inner.parser.has_pending_element() => {
+                            return Some((
+                                Err(FirebaseError::Generic("Incomplete runQuery response")),
+                                QueryStreamState::Done,
+                            ))
+                        }
+                        Ok(None) => return None,
+                        Err(e) => return Some((Err(e.into()), QueryStreamState::Done)),
+                    },
+                }
+            }
+        },
+    ))
+}
+
+fn query_response_to_pod<T>(
+    response: &dto::RunQueryResponse,
+    document: &dto::Document,
+    skipped_results: i32,
+    transaction: &Option<String>,
+) -> Result<(T, DocumentMeta)>
+where
+    for<'b> T: Deserialize<'b>,
+{
+    let mut meta = DocumentMeta::from_document(document)?;
+    meta.read_time = parse_timestamp(
+        &response.read_time,
+        "Failed to parse rfc3339 date from 'read_time' field",
+    )?;
+    meta.skipped_results = skipped_results;
+    meta.transaction = transaction.clone();
+    Ok((document_to_pod(document, None)?, meta))
+}
+
+/// Deserializes the documents of the given runQuery responses and extracts their metadata
+pub(crate) fn query_responses_to_pods<T>(responses: Vec<dto::RunQueryResponse>) -> Result<Vec<(T, DocumentMeta)>>
+where
+    for<'b> T: Deserialize<'b>,
+{
+    let mut skipped_results = 0;
+    let mut transaction = None;
+    let mut results = Vec::new();
+    for response in responses {
+        skipped_results += response.skipped_results.unwrap_or_default();
+        if response.transaction.is_some() {
+            transaction = response.transaction.clone();
+        }
+        if let Some(document) = response.document.as_ref() {
+            results.push(query_response_to_pod(
+                &response,
+                document,
+                skipped_results,
+                &transaction,
+            )?);
+            skipped_results = 0;
+        }
+    }
+    Ok(results)
+}
+
+enum QueryStreamState<AUTH> {
+    Request(AUTH, Box<dto::RunQueryRequest>, String, String),
+    Streaming(Box<QueryStreamInner>),
+    Done,
+}
+
+struct QueryStreamInner {
+    response: reqwest::Response,
+    parser: JsonArrayParser,
+    skipped_results: i32,
+    transaction: Option<String>,
+}
+
+/// Sends the given query request to the runQuery endpoint of the given parent document path.
+/// The root of the database is addressed with an empty parent. Returns the response from `reqwest`
+async fn send_query_request(
+    auth: &impl FirebaseAuthBearer,
+    query_request: &dto::RunQueryRequest,
+    parent: &str,
+    context: &str,
+) -> Result<reqwest::Response> {
+    let url = firebase_url_query(auth, parent);
+
     let resp = auth
         .client()
         .post(&url)
         .bearer_auth(auth.access_token().await)
-        .json(&query_request)
+        .json(query_request)
         .send()
         .await?;
 
-    let resp = extract_google_api_error_async(resp, || collection_id.to_owned()).await?;
+    extract_google_api_error_async(resp, || context.to_owned()).await
+}
 
+/// Sends the given query request to the runQuery endpoint and returns all responses
+pub(crate) async fn run_query_request(
+    auth: &impl FirebaseAuthBearer,
+    query_request: &dto::RunQueryRequest,
+    parent: &str,
+    context: &str,
+) -> Result<Vec<dto::RunQueryResponse>> {
+    let resp = send_query_request(auth, query_request, parent, context).await?;
     let json: Option<Vec<dto::RunQueryResponse>> = resp.json().await?;
-
-    Ok(Query(json.unwrap_or_default().into_iter()))
+    Ok(json.unwrap_or_default())
 }
 
-/// This type is returned as a result by [`query`].
+/// This type is returned as a result by [`query`] and [`run_query`].
 /// Use it as an iterator. The query API returns a list of document references, not the documents itself.
 ///
 /// If you just need the meta data like the document name or update time, you are already settled.
@@ -104,3 +339,31 @@ impl Iterator for Query {
         return None;
     }
 }
+
+#[test]
+fn query_responses_with_meta() {
+    let responses: Vec<dto::RunQueryResponse> = serde_json::from_value(serde_json::json!([
+        {"skippedResults": 2, "readTime": "2020-04-28T14:52:51.250511Z"},
+        {
+            "document": {
+                "name": "projects/p/databases/(default)/documents/tests/a",
+                "fields": {"an_int": {"integerValue": "1"}},
+                "createTime": "2020-04-28T14:52:51.250511Z",
+                "updateTime": "2020-04-28T14:52:51.250511Z"
+            },
+            "readTime": "2020-04-28T14:52:51.250511Z"
+        },
+        {"readTime": "2020-04-28T14:52:51.250511Z"},
+        {"skippedResults": 3, "readTime": "2020-04-28T14:52:51.250511Z"}
+    ]))
+    .unwrap();
+    let results: Vec<(serde_json::Value, DocumentMeta)> = query_responses_to_pods(responses).unwrap();
+    // Trailing skipped results have no document to be reported with
+    assert_eq!(results.len(), 1);
+    let (doc, meta) = &results[0];
+    assert_eq!(doc["an_int"], 1);
+    assert_eq!(meta.id, "a");
+    assert_eq!(meta.path, "tests/a");
+    assert_eq!(meta.skipped_results, 2);
+    assert!(meta.update_time.is_some() && meta.read_time.is_some());
+}
@@ -0,0 +1,439 @@
+use super::*;
+use crate::firebase_rest_to_rust::serde_value_to_firebase_value;
+
+/// A filter for a [`QueryBuilder`].
+///
+/// Filters can be combined with [`QueryFilter::and`] and [`QueryFilter::or`] to form composite filters.
+///
+/// Example:
+/// ```
+/// use firestore_db_and_auth::{documents::QueryFilter, dto};
+///
+/// let filter = QueryFilter::or(vec![
+///     QueryFilter::field("type", dto::FieldOperator::EQUAL, "car".into()),
+///     QueryFilter::and(vec![
+///         QueryFilter::field("wheels", dto::FieldOperator::GREATER_THAN, 2.into()),
+///         QueryFilter::is_not_null("owner"),
+///     ]),
+/// ]);
+/// ```
+#[derive(Clone, Debug)]
+pub enum QueryFilter {
+    /// Compare a field with a value
+    Field(String, dto::FieldOperator, serde_json::Value),
+    /// Check a field with a unary operator, for example "IS_NULL"
+    Unary(String, dto::UnaryOperator),
+    /// All of the given filters must match
+    And(Vec<QueryFilter>),
+    /// At least one of the given filters must match
+    Or(Vec<QueryFilter>),
+}
+
+impl QueryFilter {
+    /// Compare the given field with a value. For example `"type" EQUAL "car"`.
+    pub fn field(field: impl Into<String>, operator: dto::FieldOperator, value: serde_json::Value) -> Self {
+        QueryFilter::Field(field.into(), operator, value)
+    }
+
+    /// Matches documents where the given field is null
+    pub fn is_null(field: impl Into<String>) -> Self {
+        QueryFilter::Unary(field.into(), dto::UnaryOperator::IS_NULL)
+    }
+
+    /// Matches documents where the given field is not null
+    pub fn is_not_null(field: impl Into<String>) -> Self {
+        QueryFilter::Unary(field.into(), dto::UnaryOperator::IS_NOT_NULL)
+    }
+
+    /// Matches documents where the given field is NaN
+    pub fn is_nan(field: impl Into<String>) -> Self {
+        QueryFilter::Unary(field.into(), dto::UnaryOperator::IS_NAN)
+    }
+
+    /// Matches documents where the given field is not NaN
+    pub fn is_not_nan(field: impl Into<String>) -> Self {
+        QueryFilter::Unary(field.into(), dto::UnaryOperator::IS_NOT_NAN)
+    }
+
+    /// Matches documents that satisfy all of the given filters
+    pub fn and(filters: Vec<QueryFilter>) -> Self {
+        QueryFilter::And(filters)
+    }
+
+    /// Matches documents that satisfy at least one of the given filters
+    pub fn or(filters: Vec<QueryFilter>) -> Self {
+        QueryFilter::Or(filters)
+    }
+}
+
+impl From<&QueryFilter> for dto::Filter {
+    fn from(filter: &QueryFilter) -> Self {
+        match filter {
+            QueryFilter::Field(field, op, value) => dto::Filter {
+                field_filter: Some(dto::FieldFilter {
+                    field: dto::FieldReference {
+                        field_path: field.to_owned(),
+                    },
+                    op: op.clone(),
+                    value: serde_value_to_firebase_value(value),
+                }),
+                ..Default::default()
+            },
+            QueryFilter::Unary(field, op) => dto::Filter {
+                unary_filter: Some(dto::UnaryFilter {
+                    field: dto::FieldReference {
+                        field_path: field.to_owned(),
+                    },
+                    op: op.clone(),
+                }),
+                ..Default::default()
+            },
+            QueryFilter::And(filters) => composite_filter(dto::CompositeOperator::AND, filters),
+            QueryFilter::Or(filters) => composite_filter(dto::CompositeOperator::OR, filters),
+        }
+    }
+}
+
+fn composite_filter(op: dto::CompositeOperator, filters: &[QueryFilter]) -> dto::Filter {
+    dto::Filter {
+        composite_filter: Some(dto::CompositeFilter {
+            filters: filters.iter().map(dto::Filter::from).collect(),
+            op,
+        }),
+        ..Default::default()
+    }
+}
+
+/// Splits a collection path like "a/nested/collection" into the parent document path "a/nested"
+/// and the collection id "collection". The parent is empty for root collections.
+pub(crate) fn split_collection_path(collection_path: &str) -> (&str, &str) {
+    match collection_path.trim_matches('/').rsplit_once('/') {
+        Some((parent, collection_id)) => (parent, collection_id),
+        None => ("", collection_path.trim_matches('/')),
+    }
+}
+
+fn cursor(values: Vec<serde_json::Value>, before: bool) -> dto::Cursor {
+    dto::Cursor {
+        values: Some(values.iter().map(serde_value_to_firebase_value).collect()),
+        before: Some(before),
+    }
+}
+
+/// A structured query on a collection. Execute it with [`run_query`].
+///
+/// Filters added via [`QueryBuilder::filter`] are combined with a logical AND.
+/// Use [`QueryFilter::or`] for alternatives.
+///
+/// A query usually targets a single collection, see [`QueryBuilder::new`].
+/// Use [`QueryBuilder::collection_group`] to query all collections with a given id, at any depth.
+/// Both kinds of queries can be restricted to the descendants of a document with [`QueryBuilder::parent`].
+///
+/// Example:
+/// ```no_run
+/// use firestore_db_and_auth::{documents, documents::{QueryBuilder, QueryFilter}, dto};
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// let query = QueryBuilder::new("tests")
+///     .filter(QueryFilter::field("type", dto::FieldOperator::EQUAL, "car".into()))
+///     .filter(QueryFilter::is_not_null("owner"))
+///     .order_by("wheels", dto::Direction::DESCENDING)
+///     .limit(10);
+/// for metadata in documents::run_query(&session, &query).await.unwrap() {
+///     println!("id: {}", &metadata.name);
+/// }
+/// # })
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct QueryBuilder {
+    parent: String,
+    /// The parent document of the collection path given to [`QueryBuilder::new`],
+    /// relative to the document given to [`QueryBuilder::parent`]
+    collection_parent: String,
+    collection_id: String,
+    all_descendants: bool,
+    filters: Vec<QueryFilter>,
+    order_by: Vec<dto::Order>,
+    start_at: Option<dto::Cursor>,
+    end_at: Option<dto::Cursor>,
+    offset: Option<i32>,
+    limit: Option<i32>,
+    select: Option<Vec<String>>,
+    read_time: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl QueryBuilder {
+    /// Create a query for the given collection path, for example "my_collection" or "a/nested/collection"
+    pub fn new(collection_path: impl AsRef<str>) -> Self {
+        let (parent, collection_id) = split_collection_path(collection_path.as_ref());
+        QueryBuilder {
+            parent: parent.to_owned(),
+            collection_parent: parent.to_owned(),
+            collection_id: collection_id.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// Create a collection group query. This queries all collections with the given id,
+    /// for example all "comments" collections, no matter how deeply they are nested.
+    ///
+    /// Firestore requires a collection group index for most filters and orderings on such queries.
+    pub fn collection_group(collection_id: impl Into<String>) -> Self {
+        QueryBuilder {
+            collection_id: collection_id.into(),
+            all_descendants: true,
+            ..Default::default()
+        }
+    }
+
+    /// Restrict the query to the descendants of the given document path, for example "users/abc".
+    ///
+    /// For a query created with [`QueryBuilder::new`] the collection path is relative to this document.
+    /// Calling this again replaces the previous document path.
+    pub fn parent(mut self, document_path: impl AsRef<str>) -> Self {
+        let document_path = document_path.as_ref().trim_matches('/');
+        self.parent = match (document_path.is_empty(), self.collection_parent.is_empty()) {
+            (_, true) => document_path.to_owned(),
+            (true, false) => self.collection_parent.clone(),
+            (false, false) => format!("{}/{}", document_path, self.collection_parent),
+        };
+        self
+    }
+
+    /// The collection id this query operates on
+    pub fn collection_id(&self) -> &str {
+        &self.collection_id
+    }
+
+    /// The document path this query is restricted to. Empty for the root of the database.
+    pub fn parent_path(&self) -> &str {
+        &self.parent
+    }
+
+    /// Returns true for collection group queries
+    pub fn is_collection_group(&self) -> bool {
+        self.all_descendants
+    }
+
+    /// Add a filter. Multiple filters are combined with a logical AND.
+    pub fn filter(mut self, filter: QueryFilter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Order the results by the given field. Can be called multiple times;
+    /// the first call determines the primary order.
+    pub fn order_by(mut self, field: impl Into<String>, direction: dto::Direction) -> Self {
+        self.order_by.push(dto::Order {
+            field: Some(dto::FieldReference {
+                field_path: field.into(),
+            }),
+            direction: Some(direction),
+        });
+        self
+    }
+
+    /// Only return the given field paths of the matching documents.
+    /// Use this to deserialize into a smaller projection type.
+    /// An empty list returns only the document names and metadata.
+    pub fn select<F: Into<String>>(mut self, field_paths: impl IntoIterator<Item = F>) -> Self {
+        self.select = Some(field_paths.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// The maximum number of results to return
+    pub fn limit(mut self, limit: i32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// The number of results to skip before returning the first one
+    pub fn offset(mut self, offset: i32) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Start the result set at the document with the given values for the "order by" fields (inclusive)
+    pub fn start_at(mut self, values: Vec<serde_json::Value>) -> Self {
+        self.start_at = Some(cursor(values, true));
+        self
+    }
+
+    /// Start the result set after the document with the given values for the "order by" fields (exclusive)
+    pub fn start_after(mut self, values: Vec<serde_json::Value>) -> Self {
+        self.start_at = Some(cursor(values, false));
+        self
+    }
+
+    /// End the result set at the document with the given values for the "order by" fields (inclusive)
+    pub fn end_at(mut self, values: Vec<serde_json::Value>) -> Self {
+        self.end_at = Some(cursor(values, false));
+        self
+    }
+
+    /// End the result set before the document with the given values for the "order by" fields (exclusive)
+    pub fn end_before(mut self, values: Vec<serde_json::Value>) -> Self {
+        self.end_at = Some(cursor(values, true));
+        self
+    }
+
+    /// Read the documents as they were at the given time. This must be a time within the past hour.
+    /// The read time is ignored for queries within a transaction, which read at the time of the transaction.
+    pub fn read_time(mut self, read_time: chrono::DateTime<chrono::Utc>) -> Self {
+        self.read_time = Some(read_time);
+        self
+    }
+
+    /// The formatted read time for a request, if set via [`QueryBuilder::read_time`]
+    pub(crate) fn read_time_param(&self) -> Option<String> {
+        self.read_time.as_ref().map(format_timestamp)
+    }
+
+    /// Returns the runQuery request for this query, outside of a transaction
+    pub(crate) fn query_request(&self) -> dto::RunQueryRequest {
+        dto::RunQueryRequest {
+            structured_query: Some(self.structured_query()),
+            read_time: self.read_time_param(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the structured query as it is send to the Firestore REST API
+    pub fn structured_query(&self) -> dto::StructuredQuery {
+        let where_ = match self.filters.len() {
+            0 => None,
+            1 => Some(dto::Filter::from(&self.filters[0])),
+            _ => Some(composite_filter(dto::CompositeOperator::AND, &self.filters)),
+        };
+
+        dto::StructuredQuery {
+            from: Some(vec![dto::CollectionSelector {
+                collection_id: Some(self.collection_id.clone()),
+                all_descendants: match self.all_descendants {
+                    true => Some(true),
+                    false => None,
+                },
+            }]),
+            where_,
+            order_by: match self.order_by.is_empty() {
+                true => None,
+                false => Some(self.order_by.clone()),
+            },
+            start_at: self.start_at.clone(),
+            end_at: self.end_at.clone(),
+            offset: self.offset,
+            limit: self.limit,
+            select: self.select.as_ref().map(|field_paths| dto::Projection {
+                fields: Some(
+                    field_paths
+                        .iter()
+                        .map(|field_path| dto::FieldReference {
+                            field_path: field_path.clone(),
+                        })
+                        .collect(),
+                ),
+            }),
+        }
+    }
+}
+
+#[test]
+fn query_builder_structured_query() {
+    let query = QueryBuilder::new("tests")
+        .filter(QueryFilter::field("type", dto::FieldOperator::EQUAL, "car".into()))
+        .filter(QueryFilter::or(vec![
+            QueryFilter::is_null("owner"),
+            QueryFilter::field("wheels", dto::FieldOperator::IN, serde_json::json!([3, 4])),
+        ]))
+        .order_by("wheels", dto::Direction::DESCENDING)
+        .start_after(vec![4.into()])
+        .limit(5)
+        .offset(2);
+
+    let json = serde_json::to_value(query.structured_query()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "from": [{"allDescendants": null, "collectionId": "tests"}],
+            "where": {"compositeFilter": {"op": "AND", "filters": [
+                {"fieldFilter": {"field": {"fieldPath": "type"}, "op": "EQUAL", "value": {"stringValue": "car"}}},
+                {"compositeFilter": {"op": "OR", "filters": [
+                    {"unaryFilter": {"field": {"fieldPath": "owner"}, "op": "IS_NULL"}},
+                    {"fieldFilter": {"field": {"fieldPath": "wheels"}, "op": "IN", "value": {"arrayValue": {"values": [
+                        {"integerValue": "3"}, {"integerValue": "4"}
+                    ]}}}}
+                ]}}
+            ]}},
+            "orderBy": [{"field": {"fieldPath": "wheels"}, "direction": "DESCENDING"}],
+            "startAt": {"values": [{"integerValue": "4"}], "before": false},
+            "limit": 5,
+            "offset": 2
+        })
+    );
+}
+
+#[test]
+fn query_builder_single_filter_is_not_wrapped() {
+    let query = QueryBuilder::new("tests").filter(QueryFilter::is_nan("value"));
+    let json = serde_json::to_value(query.structured_query()).unwrap();
+    assert_eq!(
+        json["where"],
+        serde_json::json!({"unaryFilter": {"field": {"fieldPath": "value"}, "op": "IS_NAN"}})
+    );
+    assert!(json.get("orderBy").is_none());
+    assert!(json.get("limit").is_none());
+}
+
+#[test]
+fn query_builder_select() {
+    let query = QueryBuilder::new("tests").select(["title", "author.name"]);
+    let json = serde_json::to_value(query.structured_query()).unwrap();
+    assert_eq!(
+        json["select"],
+        serde_json::json!({"fields": [{"fieldPath": "title"}, {"fieldPath": "author.name"}]})
+    );
+}
+
+#[test]
+fn query_builder_parent_and_collection_group() {
+    let query = QueryBuilder::new("users/abc/comments");
+    assert_eq!(query.parent_path(), "users/abc");
+    assert_eq!(query.collection_id(), "comments");
+
+    let query = QueryBuilder::new("comments").parent("users/abc");
+    assert_eq!(query.parent_path(), "users/abc");
+    let query = QueryBuilder::new("posts/1/comments").parent("/users/abc/");
+    assert_eq!(query.parent_path(), "users/abc/posts/1");
+    // A repeated call replaces the previous parent
+    let query = QueryBuilder::new("posts/1/comments").parent("a/b").parent("c/d");
+    assert_eq!(query.parent_path(), "c/d/posts/1");
+    let query = QueryBuilder::new("comments").parent("a/b").parent("c/d");
+    assert_eq!(query.parent_path(), "c/d");
+    let query = QueryBuilder::new("posts/1/comments").parent("a/b").parent("");
+    assert_eq!(query.parent_path(), "posts/1");
+
+    let query = QueryBuilder::collection_group("comments")
+        .parent("a/b")
+        .parent("users/abc");
+    assert_eq!(query.parent_path(), "users/abc");
+    assert!(query.is_collection_group());
+    let json = serde_json::to_value(query.structured_query()).unwrap();
+    assert_eq!(
+        json["from"],
+        serde_json::json!([{"allDescendants": true, "collectionId": "comments"}])
+    );
+}
+
+#[test]
+fn query_builder_read_time() {
+    let query = QueryBuilder::new("tests").read_time("2020-04-28T14:52:51.25Z".parse().unwrap());
+    let json = serde_json::to_value(query.query_request()).unwrap();
+    assert_eq!(json["readTime"], "2020-04-28T14:52:51.250000Z");
+    assert_eq!(json["structuredQuery"]["from"][0]["collectionId"], "tests");
+
+    let json = serde_json::to_value(QueryBuilder::new("tests").query_request()).unwrap();
+    assert!(json.get("readTime").is_none());
+}
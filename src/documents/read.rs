@@ -1,6 +1,41 @@
 use super::*;
 use std::io::Read;
 
+/// Read options for [`read_with_options`] and [`batch_get`]
+#[derive(Default, Clone, Debug)]
+pub struct ReadOptions {
+    /// If set, only the given field paths are returned, for example `vec!["title".to_owned()]`.
+    /// Use this to deserialize into a smaller projection type.
+    pub mask: Option<Vec<String>>,
+    /// If set, documents are read at the given time. This must be a time within the past hour.
+    pub read_time: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ReadOptions {
+    pub(crate) fn document_mask(&self) -> Option<dto::DocumentMask> {
+        self.mask.as_ref().map(|field_paths| dto::DocumentMask {
+            field_paths: field_paths.clone(),
+        })
+    }
+
+    /// The url query parameters for these options
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = mask_params(&self.mask);
+        if let Some(read_time) = self.read_time.as_ref() {
+            params.push(("readTime", format_timestamp(read_time)));
+        }
+        params
+    }
+}
+
+/// The url query parameters for the given field mask
+pub(crate) fn mask_params(mask: &Option<Vec<String>>) -> Vec<(&'static str, String)> {
+    mask.iter()
+        .flatten()
+        .map(|field_path| ("mask.fieldPaths", field_path.clone()))
+        .collect()
+}
+
 ///
 /// Read a document of a specific type from a collection by its Firestore document name
 ///
@@ -11,7 +46,30 @@ pub async fn read_by_name<T>(auth: &impl FirebaseAuthBearer, document_name: &str
 where
     for<'b> T: Deserialize<'b>,
 {
-    let resp = request_document(auth, document_name).await?;
+    read_by_name_with_params(auth, document_name, &[]).await
+}
+
+/// Read a document of a specific type by its Firestore document name.
+/// The given url query parameters are added to the request, for example a transaction id.
+pub(crate) async fn read_by_name_with_params<T>(
+    auth: &impl FirebaseAuthBearer,
+    document_name: &str,
+    params: &[(&str, &str)],
+) -> Result<T>
+where
+    for<'b> T: Deserialize<'b>,
+{
+    let (document, full) = request_document_json(auth, document_name, params).await?;
+    Ok(document_to_pod(&document, Some(&full))?)
+}
+
+/// Read a document by its Firestore document name and return the parsed document and the raw response
+async fn request_document_json(
+    auth: &impl FirebaseAuthBearer,
+    document_name: &str,
+    params: &[(&str, &str)],
+) -> Result<(dto::Document, bytes::Bytes)> {
+    let resp = request_document(auth, document_name, params).await?;
 
     // We take the raw response first in order to provide
     // more complete errors on deserialization failure
@@ -22,7 +80,7 @@ where
         ser: e,
     })?;
 
-    Ok(document_to_pod(&json, Some(&full))?)
+    Ok((json, full))
 }
 
 ///
@@ -32,14 +90,102 @@ where
 /// * `auth` The authentication token
 /// * `path` The document path / collection; For example `my_collection` or `a/nested/collection`
 /// * `document_id` The document id. Make sure that you do not include the document id to the path argument.
-pub async fn read<T>(auth: &impl FirebaseAuthBearer, path: &str, document_id: &str) -> Result<T>
+pub async fn read<T>(auth: &impl FirebaseAuthBearer, path: impl AsRef<str>, document_id: &str) -> Result<T>
 where
     for<'b> T: Deserialize<'b>,
 {
-    let document_name = document_name(&auth.project_id(), path, document_id);
+    let document_name = document_name(auth, path.as_ref(), document_id)?;
     read_by_name(auth, &document_name).await
 }
 
+///
+/// Read a document of a specific type from a collection with the given options.
+/// Use a field mask to only transfer and deserialize some fields into a smaller projection type.
+///
+/// Example:
+/// ```no_run
+/// use serde::Deserialize;
+/// #[derive(Debug, Deserialize)]
+/// struct TitleOnly { title: String, }
+///
+/// use firestore_db_and_auth::documents;
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// let options = documents::ReadOptions { mask: Some(vec!["title".to_owned()]), ..Default::default() };
+/// let doc: TitleOnly = documents::read_with_options(&session, "books", "book_id", options).await.unwrap();
+/// println!("{:?}", doc);
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * `auth` The authentication token
+/// * `path` The document path / collection; For example `my_collection` or `a/nested/collection`
+/// * `document_id` The document id. Make sure that you do not include the document id to the path argument.
+/// * `options` Read options like a field mask or a read time
+pub async fn read_with_options<T>(
+    auth: &impl FirebaseAuthBearer,
+    path: impl AsRef<str>,
+    document_id: &str,
+    options: ReadOptions,
+) -> Result<T>
+where
+    for<'b> T: Deserialize<'b>,
+{
+    let document_name = document_name(auth, path.as_ref(), document_id)?;
+    let params = options.params();
+    let params: Vec<(&str, &str)> = params.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    read_by_name_with_params(auth, &document_name, &params).await
+}
+
+///
+/// Read a document of a specific type from a collection together with its metadata,
+/// like the document id and path and the create and update time.
+///
+/// Example:
+/// ```no_run
+/// use serde::Deserialize;
+/// #[derive(Debug, Deserialize)]
+/// struct Book { title: String, }
+///
+/// use firestore_db_and_auth::documents;
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// let (book, meta): (Book, _) = documents::read_with_meta(&session, "books", "book_id", documents::ReadOptions::default())
+///     .await
+///     .unwrap();
+/// println!("{} {:?}, last changed at {:?}", meta.path, book, meta.update_time);
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * `auth` The authentication token
+/// * `path` The document path / collection; For example `my_collection` or `a/nested/collection`
+/// * `document_id` The document id. Make sure that you do not include the document id to the path argument.
+/// * `options` Read options like a field mask or a read time
+pub async fn read_with_meta<T>(
+    auth: &impl FirebaseAuthBearer,
+    path: impl AsRef<str>,
+    document_id: &str,
+    options: ReadOptions,
+) -> Result<(T, DocumentMeta)>
+where
+    for<'b> T: Deserialize<'b>,
+{
+    let document_name = document_name(auth, path.as_ref(), document_id)?;
+    let params = options.params();
+    let params: Vec<(&str, &str)> = params.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    let (document, full) = request_document_json(auth, &document_name, &params).await?;
+    let mut meta = DocumentMeta::from_document(&document)?;
+    meta.read_time = options.read_time;
+    Ok((document_to_pod(&document, Some(&full))?, meta))
+}
+
 /// Return the raw unparsed content of the Firestore document. Methods like
 /// [`read()`](../documents/fn.read.html) will deserialize the JSON-encoded
 /// response into a known type `T`
@@ -47,19 +193,24 @@ where
 /// Note that this leverages [`std::io::Read`](https://doc.rust-lang.org/std/io/trait.Read.html) and the `read_to_string()` method to chunk the
 /// response. This will raise `FirebaseError::IO` if there are errors reading the stream. Please
 /// see [`read_to_end()`](https://doc.rust-lang.org/std/io/trait.Read.html#method.read_to_end)
-pub async fn contents(auth: &impl FirebaseAuthBearer, path: &str, document_id: &str) -> Result<String> {
-    let document_name = document_name(&auth.project_id(), path, document_id);
-    let resp = request_document(auth, &document_name).await?;
+pub async fn contents(auth: &impl FirebaseAuthBearer, path: impl AsRef<str>, document_id: &str) -> Result<String> {
+    let document_name = document_name(auth, path.as_ref(), document_id)?;
+    let resp = request_document(auth, &document_name, &[]).await?;
     resp.text().await.map_err(|e| FirebaseError::Request(e))
 }
 
 /// Executes the request to retrieve the document. Returns the response from `reqwest`
-async fn request_document(auth: &impl FirebaseAuthBearer, document_name: &str) -> Result<reqwest::Response> {
-    let url = firebase_url_base(document_name.as_ref());
+async fn request_document(
+    auth: &impl FirebaseAuthBearer,
+    document_name: &str,
+    params: &[(&str, &str)],
+) -> Result<reqwest::Response> {
+    let url = firebase_url_base(auth, document_name.as_ref());
 
     let resp = auth
         .client()
         .get(&url)
+        .query(params)
         .bearer_auth(auth.access_token().await)
         .send()
         .await?;
@@ -67,32 +218,56 @@ async fn request_document(auth: &impl FirebaseAuthBearer, document_name: &str) -
     extract_google_api_error_async(resp, || document_name.to_owned()).await
 }
 
-/// Simple method to join the path and document identifier in correct format
-fn document_name(project_id: &str, path: &str, document_id: &str) -> String {
-    format!(
-        "projects/{}/databases/(default)/documents/{}/{}",
-        project_id, path, document_id
-    )
+/// Joins the collection path and document identifier into a full document name.
+/// Fails if the path is not a valid collection path or the document id is invalid.
+pub(crate) fn document_name(auth: &impl FirebaseAuthBearer, path: &str, document_id: &str) -> Result<String> {
+    Ok(CollectionRef::new(path)?.doc(document_id)?.name(auth))
 }
 
 #[test]
 fn it_document_name_joins_paths() {
-    let project_id = "firebase-project";
+    let auth = tests::TestAuth::new();
     let path = "one/two/three";
     let document_id = "my-document";
     assert_eq!(
-        document_name(&project_id, &path, &document_id),
-        "projects/firebase-project/databases/(default)/documents/one/two/three/my-document"
+        document_name(&auth, &path, &document_id).unwrap(),
+        "projects/project/databases/(default)/documents/one/two/three/my-document"
+    );
+
+    let auth = tests::TestAuth(reqwest::Client::new(), "named");
+    assert_eq!(
+        document_name(&auth, &path, &document_id).unwrap(),
+        "projects/project/databases/named/documents/one/two/three/my-document"
     );
 }
 
 #[test]
-fn it_document_name_joins_invalid_path_fragments() {
-    let project_id = "firebase-project";
+fn it_document_name_rejects_invalid_path_fragments() {
+    let auth = tests::TestAuth::new();
     let path = "one/two//three/";
     let document_id = "///my-document";
+    assert!(document_name(&auth, &path, &document_id).is_err());
+    assert!(document_name(&auth, "one/two/three", "my/document").is_err());
+    assert!(document_name(&auth, "one/two", "my-document").is_err());
+}
+
+#[test]
+fn read_options_params() {
+    let options = ReadOptions {
+        mask: Some(vec!["title".to_owned(), "`a.b`".to_owned()]),
+        read_time: Some(
+            chrono::DateTime::parse_from_rfc3339("2020-04-28T14:52:51Z")
+                .unwrap()
+                .into(),
+        ),
+    };
     assert_eq!(
-        document_name(&project_id, &path, &document_id),
-        "projects/firebase-project/databases/(default)/documents/one/two//three/////my-document"
+        options.params(),
+        vec![
+            ("mask.fieldPaths", "title".to_owned()),
+            ("mask.fieldPaths", "`a.b`".to_owned()),
+            ("readTime", "2020-04-28T14:52:51.000000Z".to_owned())
+        ]
     );
+    assert!(ReadOptions::default().params().is_empty());
 }
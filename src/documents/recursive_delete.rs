@@ -0,0 +1,450 @@
+use super::*;
+use futures::future::BoxFuture;
+use futures::stream::{FuturesUnordered, StreamExt};
+use futures::FutureExt;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+/// Called with the relative paths of a batch of documents that has just been deleted, or that would
+/// be deleted in a dry run, and the total number of documents so far. See [`RecursiveDeleteOptions`].
+pub type RecursiveDeleteProgress = Arc<dyn Fn(&[String], usize) + Send + Sync>;
+
+/// Options for [`recursive_delete_with_options`]
+#[derive(Clone)]
+pub struct RecursiveDeleteOptions {
+    /// The maximum number of delete batches that are committed at the same time. Defaults to 10.
+    pub concurrency: usize,
+    /// Only find the documents that would be deleted and report them via `on_progress`, without deleting anything
+    pub dry_run: bool,
+    /// Called whenever a batch of documents has been deleted or, in a dry run, has been found
+    pub on_progress: Option<RecursiveDeleteProgress>,
+}
+
+impl Default for RecursiveDeleteOptions {
+    fn default() -> Self {
+        RecursiveDeleteOptions {
+            concurrency: 10,
+            dry_run: false,
+            on_progress: None,
+        }
+    }
+}
+
+///
+/// Deletes the document or collection at the given path together with all documents of all
+/// subcollections, at any depth. Returns the number of deleted documents.
+///
+/// See [`recursive_delete_with_options`] for a dry run and progress reports.
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'path' The relative path of a document, like "users/abc", or of a collection, like "users/abc/posts"
+pub async fn recursive_delete<AUTH>(auth: &AUTH, path: impl AsRef<str>) -> Result<usize>
+where
+    AUTH: FirebaseAuthBearer + Clone + Send + Sync + 'static,
+{
+    recursive_delete_with_options(auth, path, RecursiveDeleteOptions::default()).await
+}
+
+///
+/// Deletes the document or collection at the given path together with all documents of all
+/// subcollections, at any depth. Returns the number of deleted documents.
+///
+/// All descendant collections are walked, including those below documents that do not exist themselves.
+/// Documents are deleted bottom-up in batches of up to [`MAX_BATCH_WRITES`] documents,
+/// with up to [`RecursiveDeleteOptions::concurrency`] batches at the same time.
+/// Batches are committed in the background while the walk continues, which requires a tokio runtime.
+/// A document is only committed for deletion after all batches with its descendants have been committed.
+/// The document at the given path is deleted last, after all of its descendants have been deleted.
+///
+/// The deletion is not atomic. If an error is returned, some documents may have been deleted already.
+///
+/// Example:
+/// ```no_run
+/// use std::sync::Arc;
+/// use firestore_db_and_auth::documents;
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// // List what would be deleted
+/// let options = documents::RecursiveDeleteOptions {
+///     dry_run: true,
+///     on_progress: Some(Arc::new(|paths: &[String], _| paths.iter().for_each(|path| println!("{}", path)))),
+///     ..Default::default()
+/// };
+/// let count = documents::recursive_delete_with_options(&session, "users/abc", options).await.unwrap();
+/// println!("Would delete {} documents", count);
+///
+/// // Delete, with progress reports
+/// let options = documents::RecursiveDeleteOptions {
+///     on_progress: Some(Arc::new(|_: &[String], count| println!("Deleted {} documents", count))),
+///     ..Default::default()
+/// };
+/// documents::recursive_delete_with_options(&session, "users/abc", options).await.unwrap();
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'path' The relative path of a document, like "users/abc", or of a collection, like "users/abc/posts"
+/// * 'options' Concurrency, dry run and progress reporting
+pub async fn recursive_delete_with_options<AUTH>(
+    auth: &AUTH,
+    path: impl AsRef<str>,
+    options: RecursiveDeleteOptions,
+) -> Result<usize>
+where
+    AUTH: FirebaseAuthBearer + Clone + Send + Sync + 'static,
+{
+    let path = path.as_ref().trim_matches('/');
+    let is_document = path.split('/').count() % 2 == 0;
+    if is_document {
+        DocumentRef::new(path)?;
+    } else {
+        CollectionRef::new(path)?;
+    }
+
+    let commit_batch = |paths: Vec<String>| {
+        let auth = auth.clone();
+        async move {
+            let writes: Vec<dto::Write> = paths
+                .iter()
+                .map(|path| dto::Write {
+                    delete: Some(firebase_document_name(&auth, path)),
+                    ..Default::default()
+                })
+                .collect();
+            commit(&auth, writes, None).await?;
+            Ok(paths)
+        }
+        .boxed()
+    };
+    let mut deleter = Deleter::new(&options, MAX_BATCH_WRITES, Box::new(commit_batch));
+
+    match delete_tree(auth, path, is_document, &mut deleter).await {
+        Ok(()) => Ok(deleter.count),
+        Err(e) => {
+            // Batches that have been sent must not keep deleting after an error has been returned
+            deleter.settle().await;
+            Err(e)
+        }
+    }
+}
+
+/// Walks the document tree below the given path and pushes all existing documents to the deleter,
+/// descendants first
+async fn delete_tree<AUTH>(auth: &AUTH, path: &str, is_document: bool, deleter: &mut Deleter<'_>) -> Result<()>
+where
+    AUTH: FirebaseAuthBearer + Clone + Send + Sync + 'static,
+{
+    let mut steps = vec![match is_document {
+        true => Step::ListCollectionIds(path.to_owned()),
+        false => Step::ListDocuments(path.to_owned()),
+    }];
+    while let Some(step) = steps.pop() {
+        match step {
+            Step::ListCollectionIds(document_path) => {
+                let mut collection_ids = list_collection_ids(auth, &document_path);
+                while let Some(collection_id) = collection_ids.next().await {
+                    steps.push(Step::ListDocuments(format!("{}/{}", document_path, collection_id?)));
+                }
+            }
+            Step::ListDocuments(collection_path) => {
+                let params = ListOptions {
+                    show_missing: true,
+                    ..Default::default()
+                }
+                .params();
+                let mut documents = list_documents(auth, collection_path.clone(), params);
+                while let Some(document) = documents.next().await {
+                    let document = document?;
+                    let document_path = child_document_path(&collection_path, &document.name);
+                    // Missing documents only exist because of their subcollections
+                    if document.create_time.is_some() || document.update_time.is_some() {
+                        steps.push(Step::Delete(document_path.clone()));
+                    }
+                    steps.push(Step::ListCollectionIds(document_path));
+                }
+            }
+            Step::Delete(document_path) => deleter.push(document_path).await?,
+        }
+    }
+    deleter.finish().await?;
+
+    if is_document && document_exists(auth, path).await? {
+        deleter.push(path.to_owned()).await?;
+        deleter.finish().await?;
+    }
+    Ok(())
+}
+
+/// A step of the walk through the document tree. The steps are kept on a stack,
+/// so that the descendants of a document are processed before the document itself.
+enum Step {
+    ListCollectionIds(String),
+    ListDocuments(String),
+    Delete(String),
+}
+
+/// Returns the relative path of the document with the given absolute name within the given collection
+fn child_document_path(collection_path: &str, document_name: &str) -> String {
+    let document_id = document_name.rsplit('/').next().unwrap_or_default();
+    format!("{}/{}", collection_path, document_id)
+}
+
+async fn document_exists<AUTH>(auth: &AUTH, path: &str) -> Result<bool>
+where
+    AUTH: FirebaseAuthBearer + Clone + Send + Sync + 'static,
+{
+    // An empty mask only returns the document name and metadata
+    let options = ReadOptions {
+        mask: Some(Vec::new()),
+        ..Default::default()
+    };
+    let mut results = batch_get::<serde_json::Value, _>(auth, &[path], options);
+    match results.next().await {
+        Some(result) => Ok(result?.is_some()),
+        None => Ok(false),
+    }
+}
+
+/// Commits a batch of document paths for deletion and returns the paths
+type CommitBatch<'a> = Box<dyn Fn(Vec<String>) -> BoxFuture<'static, Result<Vec<String>>> + 'a>;
+
+/// Collects document paths into batches and commits them in the background with bounded concurrency
+struct Deleter<'a> {
+    options: &'a RecursiveDeleteOptions,
+    batch_size: usize,
+    commit_batch: CommitBatch<'a>,
+    pending: Vec<String>,
+    in_flight: FuturesUnordered<JoinHandle<Result<Vec<String>>>>,
+    /// The depth of the deepest document in a batch that has been sent since the in-flight batches
+    /// were last awaited. Zero if there are no such batches.
+    in_flight_depth: usize,
+    count: usize,
+}
+
+impl<'a> Deleter<'a> {
+    fn new(options: &'a RecursiveDeleteOptions, batch_size: usize, commit_batch: CommitBatch<'a>) -> Self {
+        Deleter {
+            options,
+            batch_size,
+            commit_batch,
+            pending: Vec::new(),
+            in_flight: FuturesUnordered::new(),
+            in_flight_depth: 0,
+            count: 0,
+        }
+    }
+
+    async fn push(&mut self, path: String) -> Result<()> {
+        self.reap_finished()?;
+        // Descendants are pushed before their ancestors. Batches with deeper documents may contain
+        // descendants of this document, which must be deleted before this document is committed.
+        if self.in_flight_depth > path_depth(&path) {
+            self.wait_in_flight().await?;
+        }
+        self.pending.push(path);
+        if self.pending.len() >= self.batch_size {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Commits the pending paths as one batch in the background, after waiting for a free slot
+    async fn flush(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let paths = std::mem::take(&mut self.pending);
+        if self.options.dry_run {
+            self.report(&paths);
+            return Ok(());
+        }
+
+        while self.in_flight.len() >= self.options.concurrency.max(1) {
+            if let Some(result) = self.in_flight.next().await {
+                self.report(&batch_result(result)?);
+            }
+        }
+
+        let depth = paths.iter().map(|path| path_depth(path)).max().unwrap_or_default();
+        self.in_flight_depth = self.in_flight_depth.max(depth);
+        self.in_flight.push(tokio::spawn((self.commit_batch)(paths)));
+        Ok(())
+    }
+
+    /// Commits the pending paths and waits for all batches to complete
+    async fn finish(&mut self) -> Result<()> {
+        self.flush().await?;
+        self.wait_in_flight().await
+    }
+
+    /// Waits for all batches that have been sent so far
+    async fn wait_in_flight(&mut self) -> Result<()> {
+        while let Some(result) = self.in_flight.next().await {
+            self.report(&batch_result(result)?);
+        }
+        self.in_flight_depth = 0;
+        Ok(())
+    }
+
+    /// Reports the batches that have completed in the meantime, without waiting
+    fn reap_finished(&mut self) -> Result<()> {
+        while let Some(Some(result)) = self.in_flight.next().now_or_never() {
+            self.report(&batch_result(result)?);
+        }
+        if self.in_flight.is_empty() {
+            self.in_flight_depth = 0;
+        }
+        Ok(())
+    }
+
+    /// Waits for all batches that have been sent so far after an error, reporting the successful ones
+    async fn settle(&mut self) {
+        while let Some(result) = self.in_flight.next().await {
+            if let Ok(paths) = batch_result(result) {
+                self.report(&paths);
+            }
+        }
+        self.in_flight_depth = 0;
+    }
+
+    fn report(&mut self, paths: &[String]) {
+        self.count += paths.len();
+        if let Some(on_progress) = self.options.on_progress.as_ref() {
+            on_progress(paths, self.count);
+        }
+    }
+}
+
+impl Drop for Deleter<'_> {
+    /// Stops the batches that are still running if the deletion has been cancelled
+    fn drop(&mut self) {
+        self.in_flight.iter().for_each(JoinHandle::abort);
+    }
+}
+
+fn batch_result(result: std::result::Result<Result<Vec<String>>, tokio::task::JoinError>) -> Result<Vec<String>> {
+    result.unwrap_or(Err(FirebaseError::Generic("A delete batch has panicked")))
+}
+
+/// The number of path segments of the given relative document path
+fn path_depth(path: &str) -> usize {
+    path.split('/').count()
+}
+
+#[test]
+fn recursive_delete_child_document_path() {
+    assert_eq!(
+        child_document_path(
+            "users/abc/posts",
+            "projects/p/databases/(default)/documents/users/abc/posts/1"
+        ),
+        "users/abc/posts/1"
+    );
+}
+
+#[test]
+fn recursive_delete_path_depth() {
+    assert_eq!(path_depth("users/abc"), 2);
+    assert_eq!(path_depth("users/abc/posts/1"), 4);
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+enum BatchEvent {
+    Started(Vec<String>),
+    Completed(Vec<String>),
+}
+
+/// A batch commit that records when batches start and complete. Later batches complete faster,
+/// so that the order would be wrong without waiting for the batches with descendants.
+#[cfg(test)]
+fn recording_commit_batch(
+    events: Arc<std::sync::Mutex<Vec<BatchEvent>>>,
+    fail_on: Option<&'static str>,
+) -> CommitBatch<'static> {
+    let started = std::sync::atomic::AtomicU64::new(0);
+    Box::new(move |paths: Vec<String>| {
+        let events = events.clone();
+        let index = started.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        events.lock().unwrap().push(BatchEvent::Started(paths.clone()));
+        async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50u64.saturating_sub(index * 10))).await;
+            if paths.iter().any(|path| Some(path.as_str()) == fail_on) {
+                return Err(FirebaseError::Generic("Commit failed"));
+            }
+            events.lock().unwrap().push(BatchEvent::Completed(paths.clone()));
+            Ok(paths)
+        }
+        .boxed()
+    })
+}
+
+#[tokio::test]
+async fn recursive_delete_children_before_parents() {
+    // In the order of the walk: descendants before their ancestors
+    let paths = [
+        "a/1/b/1/c/1",
+        "a/1/b/1/c/2",
+        "a/1/b/1",
+        "a/1/b/2",
+        "a/2/b/1",
+        "a/1",
+        "a/2",
+    ];
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let options = RecursiveDeleteOptions::default();
+    let mut deleter = Deleter::new(&options, 2, recording_commit_batch(events.clone(), None));
+    for path in paths {
+        deleter.push(path.to_owned()).await.unwrap();
+    }
+    deleter.finish().await.unwrap();
+    assert_eq!(deleter.count, paths.len());
+
+    let events = events.lock().unwrap();
+    for (index, event) in events.iter().enumerate() {
+        let started = match event {
+            BatchEvent::Started(started) => started,
+            BatchEvent::Completed(_) => continue,
+        };
+        let completed: Vec<&String> = events[..index]
+            .iter()
+            .filter_map(|event| match event {
+                BatchEvent::Completed(paths) => Some(paths),
+                BatchEvent::Started(_) => None,
+            })
+            .flatten()
+            .collect();
+        for parent in started {
+            for child in paths.iter().filter(|path| path.starts_with(&format!("{}/", parent))) {
+                assert!(
+                    completed.iter().any(|path| path == child),
+                    "{} was committed before {} has been deleted: {:?}",
+                    parent,
+                    child,
+                    events
+                );
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn recursive_delete_settles_after_error() {
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let options = RecursiveDeleteOptions::default();
+    let mut deleter = Deleter::new(&options, 1, recording_commit_batch(events.clone(), Some("a/2/b/1")));
+    // The slow first batch succeeds, the faster second batch fails
+    deleter.push("a/1/b/1".to_owned()).await.unwrap();
+    deleter.push("a/2/b/1".to_owned()).await.unwrap();
+    assert!(deleter.push("a/1".to_owned()).await.is_err());
+
+    deleter.settle().await;
+    assert!(deleter.in_flight.is_empty());
+    assert_eq!(deleter.count, 1);
+    assert!(matches!(events.lock().unwrap().last(), Some(BatchEvent::Completed(paths)) if paths == &["a/1/b/1"]));
+}
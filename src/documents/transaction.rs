@@ -0,0 +1,347 @@
+use super::*;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// How often a transaction is attempted before [`run_transaction`] gives up
+const MAX_TRANSACTION_ATTEMPTS: u32 = 5;
+
+/// A handle to a running transaction, see [`run_transaction`] and [`run_read_only_transaction`].
+///
+/// Reads are performed within the transaction. Writes are collected and committed
+/// atomically when the transaction function returns successfully.
+/// Firestore requires all reads of a transaction to happen before its writes.
+///
+/// The handle is cheap to clone. All clones refer to the same transaction.
+pub struct Transaction<'a, AUTH> {
+    auth: &'a AUTH,
+    id: String,
+    writes: Arc<Mutex<Vec<dto::Write>>>,
+    read_only: bool,
+}
+
+impl<'a, AUTH> Clone for Transaction<'a, AUTH> {
+    fn clone(&self) -> Self {
+        Transaction {
+            auth: self.auth,
+            id: self.id.clone(),
+            writes: self.writes.clone(),
+            read_only: self.read_only,
+        }
+    }
+}
+
+impl<'a, AUTH: FirebaseAuthBearer> Transaction<'a, AUTH> {
+    /// The transaction id
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns true for a read-only transaction, which does not accept writes
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Read a document of a specific type from a collection within this transaction.
+    /// See [`read`].
+    pub async fn read<T>(&self, path: impl AsRef<str>, document_id: &str) -> Result<T>
+    where
+        for<'b> T: Deserialize<'b>,
+    {
+        let document_name = document_name(self.auth, path.as_ref(), document_id)?;
+        self.read_by_name(&document_name).await
+    }
+
+    /// Read a document of a specific type by its Firestore document name within this transaction.
+    /// See [`read_by_name`].
+    pub async fn read_by_name<T>(&self, document_name: &str) -> Result<T>
+    where
+        for<'b> T: Deserialize<'b>,
+    {
+        read_by_name_with_params(self.auth, document_name, &[("transaction", &self.id)]).await
+    }
+
+    /// Execute a structured query within this transaction and return all matching documents.
+    /// See [`query_stream`]. The read time of the query is ignored.
+    pub async fn query<T>(&self, query: &QueryBuilder) -> Result<Vec<(T, DocumentMeta)>>
+    where
+        for<'b> T: Deserialize<'b>,
+    {
+        let query_request = dto::RunQueryRequest {
+            structured_query: Some(query.structured_query()),
+            transaction: Some(self.id.clone()),
+            ..Default::default()
+        };
+
+        let responses =
+            run_query_request(self.auth, &query_request, query.parent_path(), query.collection_id()).await?;
+        let mut results = query_responses_to_pods(responses)?;
+        for (_, meta) in results.iter_mut() {
+            meta.transaction = Some(self.id.clone());
+        }
+        Ok(results)
+    }
+
+    /// Write a document as part of this transaction. The write is performed when the transaction is committed.
+    /// See [`write`] for the options.
+    ///
+    /// ## Arguments
+    /// * 'path' The document path / collection; For example "my_collection" or "a/nested/collection"
+    /// * 'document_id' The document id. Make sure that you do not include the document id in the path argument.
+    /// * 'document' The document
+    /// * 'options' Write options
+    pub fn write<T>(&self, path: impl AsRef<str>, document_id: &str, document: &T, options: WriteOptions) -> Result<()>
+    where
+        T: Serialize,
+    {
+        let document_name = document_name(self.auth, path.as_ref(), document_id)?;
+        let write = document_write(document_name, document, &options)?;
+        self.push_write(write)
+    }
+
+    /// Delete a document as part of this transaction. The document is deleted when the transaction is committed.
+    ///
+    /// ## Arguments
+    /// * 'path' The relative collection path and document id, for example "my_collection/document_id"
+    pub fn delete(&self, path: impl AsRef<str>) -> Result<()> {
+        let document_name = DocumentRef::new(path)?.name(self.auth);
+        self.push_write(dto::Write {
+            delete: Some(document_name),
+            ..Default::default()
+        })
+    }
+
+    fn push_write(&self, write: dto::Write) -> Result<()> {
+        if self.read_only {
+            return Err(FirebaseError::Generic("Cannot write within a read-only transaction"));
+        }
+        self.writes.lock().unwrap().push(write);
+        Ok(())
+    }
+
+    fn take_writes(&self) -> Vec<dto::Write> {
+        std::mem::take(&mut *self.writes.lock().unwrap())
+    }
+}
+
+///
+/// Runs the given function within a read-write transaction and commits all writes of the function atomically.
+///
+/// The function receives a [`Transaction`] handle for reading and writing documents.
+/// If the function returns an error, the transaction is rolled back and the error is returned.
+///
+/// Firestore aborts transactions if a concurrent operation modified a document that has been read within the
+/// transaction. In that case the function is retried with a backoff delay (up to five attempts).
+/// The function should therefore not have side effects besides the transaction operations.
+///
+/// Example:
+/// ```no_run
+/// use serde::{Serialize, Deserialize};
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct Counter { count: u32, }
+///
+/// use firestore_db_and_auth::documents;
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// let count = documents::run_transaction(&session, |tx| async move {
+///     let mut counter: Counter = tx.read("counters", "visits").await?;
+///     counter.count += 1;
+///     tx.write("counters", "visits", &counter, documents::WriteOptions::default())?;
+///     Ok(counter.count)
+/// })
+/// .await
+/// .unwrap();
+/// println!("Visits: {}", count);
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'transaction_fn' The transaction function. It might be called multiple times.
+pub async fn run_transaction<'a, AUTH, F, Fut, R>(auth: &'a AUTH, mut transaction_fn: F) -> Result<R>
+where
+    AUTH: FirebaseAuthBearer,
+    F: FnMut(Transaction<'a, AUTH>) -> Fut,
+    Fut: Future<Output = Result<R>>,
+{
+    let mut retry_transaction = None;
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+
+        let options = dto::TransactionOptions {
+            read_write: Some(dto::ReadWrite { retry_transaction }),
+            ..Default::default()
+        };
+        let transaction = Transaction {
+            auth,
+            id: begin_transaction(auth, options).await?,
+            writes: Arc::new(Mutex::new(Vec::new())),
+            read_only: false,
+        };
+
+        let error = match transaction_fn(transaction.clone()).await {
+            Ok(value) => match commit(auth, transaction.take_writes(), Some(transaction.id.clone())).await {
+                Ok(_) => return Ok(value),
+                Err(e) => e,
+            },
+            Err(e) => {
+                // The original error is more relevant than a failed rollback
+                let _ = rollback(auth, &transaction.id).await;
+                e
+            }
+        };
+
+        match error {
+            FirebaseError::Aborted(_, _) if attempt < MAX_TRANSACTION_ATTEMPTS => {
+                tokio::time::sleep(retry_delay(attempt)).await;
+                retry_transaction = Some(transaction.id);
+            }
+            e => return Err(e),
+        }
+    }
+}
+
+///
+/// Runs the given function within a read-only transaction. All reads of the function
+/// see the same consistent snapshot of the database.
+///
+/// The snapshot is taken at the given read time, which must be within the past hour,
+/// or at the time the transaction starts. Writes via the [`Transaction`] handle are rejected.
+///
+/// Example:
+/// ```no_run
+/// use serde::Deserialize;
+/// #[derive(Debug, Deserialize)]
+/// struct Counter { count: u32, }
+///
+/// use firestore_db_and_auth::{documents, documents::QueryBuilder};
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// // Read the counters as they were ten minutes ago
+/// let read_time = chrono::Utc::now() - chrono::Duration::minutes(10);
+/// let (visits, all): (Counter, Vec<(Counter, _)>) = documents::run_read_only_transaction(&session, Some(read_time), |tx| async move {
+///     let visits = tx.read("counters", "visits").await?;
+///     let all = tx.query(&QueryBuilder::new("counters")).await?;
+///     Ok((visits, all))
+/// })
+/// .await
+/// .unwrap();
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'read_time' The time of the snapshot. The current time is used if None.
+/// * 'transaction_fn' The transaction function
+pub async fn run_read_only_transaction<'a, AUTH, F, Fut, R>(
+    auth: &'a AUTH,
+    read_time: Option<chrono::DateTime<chrono::Utc>>,
+    transaction_fn: F,
+) -> Result<R>
+where
+    AUTH: FirebaseAuthBearer,
+    F: FnOnce(Transaction<'a, AUTH>) -> Fut,
+    Fut: Future<Output = Result<R>>,
+{
+    let options = dto::TransactionOptions {
+        read_only: Some(dto::ReadOnly {
+            read_time: read_time.as_ref().map(format_timestamp),
+        }),
+        ..Default::default()
+    };
+    let transaction = Transaction {
+        auth,
+        id: begin_transaction(auth, options).await?,
+        writes: Arc::new(Mutex::new(Vec::new())),
+        read_only: true,
+    };
+
+    // A read-only transaction holds no locks and needs neither a commit nor a rollback
+    transaction_fn(transaction).await
+}
+
+/// Starts a new transaction with the given options and returns the transaction id
+async fn begin_transaction(auth: &impl FirebaseAuthBearer, options: dto::TransactionOptions) -> Result<String> {
+    let url = firebase_url_action(auth, "beginTransaction");
+
+    let resp = auth
+        .client()
+        .post(&url)
+        .bearer_auth(auth.access_token().await)
+        .json(&dto::BeginTransactionRequest { options: Some(options) })
+        .send()
+        .await?;
+
+    let resp = extract_google_api_error_async(resp, || "beginTransaction".to_owned()).await?;
+
+    let json: dto::BeginTransactionResponse = resp.json().await?;
+    json.transaction.ok_or(FirebaseError::Generic(
+        "No transaction id in the beginTransaction response",
+    ))
+}
+
+/// Commits the given writes atomically, optionally as part of a transaction
+pub(crate) async fn commit(
+    auth: &impl FirebaseAuthBearer,
+    writes: Vec<dto::Write>,
+    transaction: Option<String>,
+) -> Result<dto::CommitResponse> {
+    let existing_documents = writes.iter().any(|write| requires_existence(&write.current_document));
+    let url = firebase_url_action(auth, "commit");
+
+    let resp = auth
+        .client()
+        .post(&url)
+        .bearer_auth(auth.access_token().await)
+        .json(&dto::CommitRequest {
+            writes: Some(writes),
+            transaction,
+        })
+        .send()
+        .await?;
+
+    let resp = extract_google_write_error_async(resp, existing_documents, || "commit".to_owned()).await?;
+
+    Ok(resp.json().await?)
+}
+
+/// Rolls back the given transaction
+async fn rollback(auth: &impl FirebaseAuthBearer, transaction: &str) -> Result<()> {
+    let url = firebase_url_action(auth, "rollback");
+
+    let resp = auth
+        .client()
+        .post(&url)
+        .bearer_auth(auth.access_token().await)
+        .json(&dto::RollbackRequest {
+            transaction: Some(transaction.to_owned()),
+        })
+        .send()
+        .await?;
+
+    extract_google_api_error_async(resp, || transaction.to_owned()).await?;
+    Ok(())
+}
+
+#[test]
+fn read_only_transaction_rejects_writes() {
+    let auth = tests::TestAuth::new();
+    let transaction = Transaction {
+        auth: &auth,
+        id: "id".to_owned(),
+        writes: Arc::new(Mutex::new(Vec::new())),
+        read_only: true,
+    };
+    assert!(transaction.delete("tests/a").is_err());
+    assert!(transaction
+        .write("tests", "a", &serde_json::json!({"a": 1}), WriteOptions::default())
+        .is_err());
+    assert!(transaction.take_writes().is_empty());
+}
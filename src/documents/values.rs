@@ -0,0 +1,371 @@
+use super::*;
+use crate::firebase_rest_to_rust::serde_value_to_firebase_value;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{de, Deserializer, Serializer};
+use serde_json::{Map, Value};
+
+pub(crate) const TIMESTAMP_MARKER: &str = "__firestore_timestamp__";
+pub(crate) const BYTES_MARKER: &str = "__firestore_bytes__";
+pub(crate) const REFERENCE_MARKER: &str = "__firestore_reference__";
+pub(crate) const GEO_POINT_MARKER: &str = "__firestore_geo_point__";
+
+///
+/// A Firestore timestamp. Stored as a timestamp value instead of a string.
+///
+/// Converts from and into [`chrono::DateTime<Utc>`]. Firestore timestamps have microsecond precision.
+/// Use the [`timestamp`] serde helpers to store `chrono::DateTime<Utc>` fields as timestamps directly.
+///
+/// Example:
+/// ```
+/// use serde::{Serialize, Deserialize};
+/// use firestore_db_and_auth::documents::{FirestoreTimestamp, GeoPoint};
+/// use firestore_db_and_auth::firebase_rest_to_rust::{document_to_pod, pod_to_document};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Visit { at: FirestoreTimestamp, location: GeoPoint }
+///
+/// let visit = Visit { at: FirestoreTimestamp::now(), location: GeoPoint::new(52.52, 13.40) };
+/// let document = pod_to_document(&visit)?;
+/// assert!(document.fields.as_ref().unwrap()["at"].timestamp_value.is_some());
+///
+/// let read: Visit = document_to_pod(&document, None)?;
+/// assert_eq!(read.at, visit.at);
+/// # Ok::<(), firestore_db_and_auth::errors::FirebaseError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FirestoreTimestamp(DateTime<Utc>);
+
+impl FirestoreTimestamp {
+    /// The current time, truncated to microseconds
+    pub fn now() -> Self {
+        Self::from(Utc::now())
+    }
+
+    /// The date and time of this timestamp
+    pub fn datetime(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+impl From<DateTime<Utc>> for FirestoreTimestamp {
+    fn from(datetime: DateTime<Utc>) -> Self {
+        use chrono::Timelike;
+        let nanos = datetime.nanosecond();
+        FirestoreTimestamp(datetime.with_nanosecond(nanos - nanos % 1000).unwrap_or(datetime))
+    }
+}
+
+impl From<FirestoreTimestamp> for DateTime<Utc> {
+    fn from(timestamp: FirestoreTimestamp) -> Self {
+        timestamp.0
+    }
+}
+
+impl Serialize for FirestoreTimestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serialize_marker(serializer, TIMESTAMP_MARKER, &format_timestamp(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for FirestoreTimestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        // Timestamps are read as rfc3339 strings
+        let value = marker_or_plain(Value::deserialize(deserializer)?, TIMESTAMP_MARKER);
+        let value = value
+            .as_str()
+            .ok_or_else(|| de::Error::custom("expected a timestamp"))?;
+        let datetime = DateTime::parse_from_rfc3339(value).map_err(de::Error::custom)?;
+        Ok(FirestoreTimestamp(datetime.with_timezone(&Utc)))
+    }
+}
+
+/// A geographical point, stored as a Firestore geo point value
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GeoPoint {
+    /// The latitude in degrees. It must be in the range [-90.0, +90.0].
+    pub latitude: f64,
+    /// The longitude in degrees. It must be in the range [-180.0, +180.0].
+    pub longitude: f64,
+}
+
+impl GeoPoint {
+    /// A geo point at the given latitude and longitude in degrees
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        GeoPoint { latitude, longitude }
+    }
+}
+
+impl Serialize for GeoPoint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let point = dto::LatLng {
+            latitude: Some(self.latitude),
+            longitude: Some(self.longitude),
+        };
+        serialize_marker(serializer, GEO_POINT_MARKER, &point)
+    }
+}
+
+impl<'de> Deserialize<'de> for GeoPoint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = marker_or_plain(Value::deserialize(deserializer)?, GEO_POINT_MARKER);
+        let point = match value {
+            Value::Object(point) => point,
+            _ => return Err(de::Error::custom("expected a geo point")),
+        };
+        // Missing coordinates are zero, like in the geo point values of Firestore
+        let coordinate = |name: &str| {
+            point
+                .get(name)
+                .map_or(Some(0.0), Value::as_f64)
+                .ok_or_else(|| de::Error::custom("expected a geo point"))
+        };
+        Ok(GeoPoint {
+            latitude: coordinate("latitude")?,
+            longitude: coordinate("longitude")?,
+        })
+    }
+}
+
+///
+/// A reference to another Firestore document, stored as a reference value.
+///
+/// The reference contains the full resource name of the document, including the project and database.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DocumentReference(String);
+
+impl DocumentReference {
+    /// A reference to the document with the given relative path within the project and database of the given session
+    pub fn new(auth: &impl FirebaseAuthBearer, path: impl AsRef<str>) -> Result<Self> {
+        Ok(DocumentReference(DocumentRef::new(path)?.name(auth)))
+    }
+
+    /// A reference to the document with the given full resource name,
+    /// like "projects/{PROJECT_ID}/databases/(default)/documents/users/abc"
+    pub fn from_name(name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        DocumentRef::from_name(&name)?;
+        Ok(DocumentReference(name))
+    }
+
+    /// The full resource name of the referenced document
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// The relative path of the referenced document, like "users/abc"
+    pub fn path(&self) -> &str {
+        abs_to_rel(&self.0)
+    }
+}
+
+impl Serialize for DocumentReference {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serialize_marker(serializer, REFERENCE_MARKER, &self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for DocumentReference {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        match marker_or_plain(Value::deserialize(deserializer)?, REFERENCE_MARKER) {
+            Value::String(name) => Ok(DocumentReference(name)),
+            _ => Err(de::Error::custom("expected a document reference")),
+        }
+    }
+}
+
+/// Binary data, stored as a Firestore bytes value
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Bytes(pub Vec<u8>);
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bytes(bytes)
+    }
+}
+
+impl From<Bytes> for Vec<u8> {
+    fn from(bytes: Bytes) -> Self {
+        bytes.0
+    }
+}
+
+impl std::ops::Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serialize_marker(serializer, BYTES_MARKER, &BASE64_STANDARD.encode(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        match marker_or_plain(Value::deserialize(deserializer)?, BYTES_MARKER) {
+            Value::String(encoded) => Ok(Bytes(BASE64_STANDARD.decode(encoded).map_err(de::Error::custom)?)),
+            _ => Err(de::Error::custom("expected bytes")),
+        }
+    }
+}
+
+///
+/// Serde helpers to store a `chrono::DateTime<Utc>` as a Firestore timestamp value, see [`FirestoreTimestamp`].
+///
+/// Example:
+/// ```
+/// use serde::{Serialize, Deserialize};
+/// use firestore_db_and_auth::documents;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Event {
+///     #[serde(with = "documents::timestamp")]
+///     at: chrono::DateTime<chrono::Utc>,
+///     #[serde(with = "documents::timestamp::option", default)]
+///     ended: Option<chrono::DateTime<chrono::Utc>>,
+/// }
+/// ```
+pub mod timestamp {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(datetime: &DateTime<Utc>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        FirestoreTimestamp::from(*datetime).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<DateTime<Utc>, D::Error> {
+        FirestoreTimestamp::deserialize(deserializer).map(Into::into)
+    }
+
+    /// The serde helpers for an optional `chrono::DateTime<Utc>`
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            datetime: &Option<DateTime<Utc>>,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error> {
+            datetime.map(FirestoreTimestamp::from).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> std::result::Result<Option<DateTime<Utc>>, D::Error> {
+            Ok(Option::<FirestoreTimestamp>::deserialize(deserializer)?.map(Into::into))
+        }
+    }
+}
+
+/// Serializes a map with the given marker key as the only entry
+fn serialize_marker<S: Serializer, T: Serialize + ?Sized>(
+    serializer: S,
+    key: &'static str,
+    value: &T,
+) -> std::result::Result<S::Ok, S::Error> {
+    use serde::ser::SerializeMap;
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(key, value)?;
+    map.end()
+}
+
+pub(crate) fn marker(key: &str, value: impl Into<Value>) -> Value {
+    let mut map = Map::new();
+    map.insert(key.to_owned(), value.into());
+    Value::Object(map)
+}
+
+/// Returns the content of the given marker object, or the value itself if it is not a marker object
+fn marker_or_plain(value: Value, key: &str) -> Value {
+    match value {
+        Value::Object(mut map) if map.len() == 1 && map.contains_key(key) => map.remove(key).unwrap_or_default(),
+        value => value,
+    }
+}
+
+/// Converts the serialized form of a [`FirestoreTimestamp`], [`GeoPoint`], [`DocumentReference`] or [`Bytes`]
+/// into the corresponding Firestore value. Returns None for any other map.
+pub(crate) fn marker_to_firebase_value(map: &Map<String, Value>) -> Option<dto::Value> {
+    if map.len() != 1 {
+        return None;
+    }
+    let (key, value) = map.iter().next()?;
+    marker_content_to_firebase_value(key, &serde_value_to_firebase_value(value))
+}
+
+/// Converts the content of a marker object with the given key, like the rfc3339 string of a [`FirestoreTimestamp`],
+/// into the corresponding Firestore value. Returns None if the key is not a marker key.
+pub(crate) fn marker_content_to_firebase_value(key: &str, content: &dto::Value) -> Option<dto::Value> {
+    let value = match key {
+        TIMESTAMP_MARKER => dto::Value {
+            timestamp_value: Some(content.string_value.clone()?),
+            ..Default::default()
+        },
+        BYTES_MARKER => dto::Value {
+            bytes_value: Some(content.string_value.clone()?),
+            ..Default::default()
+        },
+        REFERENCE_MARKER => dto::Value {
+            reference_value: Some(content.string_value.clone()?),
+            ..Default::default()
+        },
+        GEO_POINT_MARKER => {
+            let fields = content.map_value.as_ref()?.fields.as_ref()?;
+            let coordinate = |name: &str| {
+                let value = fields.get(name)?;
+                value
+                    .double_value
+                    .or_else(|| value.integer_value.as_ref()?.parse().ok())
+            };
+            dto::Value {
+                geo_point_value: Some(dto::LatLng {
+                    latitude: coordinate("latitude"),
+                    longitude: coordinate("longitude"),
+                }),
+                ..Default::default()
+            }
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Converts a bytes, reference or geo point value into the serialized form of [`Bytes`], [`DocumentReference`]
+/// or [`GeoPoint`]. Returns None for any other value.
+pub(crate) fn firebase_value_to_marker(v: &dto::Value) -> Option<Value> {
+    if let Some(bytes_value) = v.bytes_value.as_ref() {
+        Some(marker(BYTES_MARKER, bytes_value.as_str()))
+    } else if let Some(reference_value) = v.reference_value.as_ref() {
+        Some(marker(REFERENCE_MARKER, reference_value.as_str()))
+    } else if let Some(geo_point_value) = v.geo_point_value.as_ref() {
+        Some(
+            GeoPoint::new(
+                geo_point_value.latitude.unwrap_or_default(),
+                geo_point_value.longitude.unwrap_or_default(),
+            )
+            .serialize(serde_json::value::Serializer)
+            .ok()?,
+        )
+    } else {
+        None
+    }
+}
+
+#[test]
+fn geo_point_deserialize() {
+    let point: GeoPoint = serde_json::from_value(serde_json::json!({"latitude": 1.5})).unwrap();
+    assert_eq!(point, GeoPoint::new(1.5, 0.0));
+
+    for value in [
+        serde_json::json!(null),
+        serde_json::json!(12),
+        serde_json::json!("abc"),
+        serde_json::json!([1.5, 2.0]),
+        serde_json::json!({"latitude": "1.5"}),
+    ] {
+        assert!(serde_json::from_value::<GeoPoint>(value).is_err());
+    }
+}
@@ -1,14 +1,22 @@
 use super::*;
+use crate::firebase_rest_to_rust::{firebase_value_to_serde_value, LiftedDocument};
+use std::collections::HashMap;
 
 /// This is returned by the write() method in a successful case.
 ///
 /// This structure contains the document id of the written document.
 #[derive(Serialize, Deserialize)]
 pub struct WriteResult {
-    ///
+    /// The time the document has been created.
+    /// Writes with field transforms (see [`FieldValue`]) or an empty update mask are sent as commit requests,
+    /// which only report the update time. This is then None, unless the write created the document,
+    /// like [`create`] or a write without document_id.
     pub create_time: Option<chrono::DateTime<chrono::Utc>>,
     pub update_time: Option<chrono::DateTime<chrono::Utc>>,
     pub document_id: String,
+    /// The server values of all field transforms (see [`FieldValue`]), by field path
+    #[serde(default)]
+    pub transform_results: HashMap<String, serde_json::Value>,
 }
 
 /// Write options. The default will overwrite a target document and not merge fields.
@@ -18,6 +26,75 @@ pub struct WriteOptions {
     /// This only works if your document type has Option fields.
     /// The write will fail, if no document_id is given or the target document does not exist yet.
     pub merge: bool,
+    /// Field transforms that are applied in addition to the field values within the document.
+    /// The key is a field path like "a_map.a_counter", see [`FieldValue`].
+    pub transforms: Vec<(String, FieldValue)>,
+    /// With `merge` set, the update mask contains the paths of all leaf fields of nested maps,
+    /// instead of the top-level fields only. Nested maps are then merged instead of being replaced.
+    pub deep_merge: bool,
+    /// Only update the given field paths, like "a_map.a_field". See [`FieldPath`] for the syntax.
+    /// Fields in the mask that are not present in the document are removed from the target document.
+    /// Takes precedence over `merge`, but does not imply an existence precondition.
+    pub update_mask: Option<Vec<String>>,
+    /// A precondition on the existence of the target document. If set to `Some(false)`, the write fails
+    /// if the document already exists. If set to `Some(true)`, the write fails if the document does not exist.
+    /// A merge write implies `Some(true)`.
+    pub exists: Option<bool>,
+    /// A precondition on the last update time of the target document. The write fails with
+    /// [`FirebaseError::PreconditionFailed`] if the document has been changed since the given time.
+    /// Use the update time of a previous read for a compare-and-swap write. Takes precedence over `exists`.
+    pub last_update_time: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl WriteOptions {
+    /// The precondition of a write with these options
+    fn precondition(&self) -> Option<dto::Precondition> {
+        precondition(
+            self.exists.or(match self.merge {
+                true => Some(true),
+                false => None,
+            }),
+            self.last_update_time.as_ref(),
+        )
+    }
+}
+
+/// Returns true if the given precondition requires the target document to exist
+pub(crate) fn requires_existence(precondition: &Option<dto::Precondition>) -> bool {
+    matches!(precondition, Some(dto::Precondition { exists: Some(true), .. }))
+}
+
+/// Creates a precondition for the given options. The update time takes precedence,
+/// because Firestore only accepts one condition.
+pub(crate) fn precondition(
+    exists: Option<bool>,
+    last_update_time: Option<&chrono::DateTime<chrono::Utc>>,
+) -> Option<dto::Precondition> {
+    match (exists, last_update_time) {
+        (_, Some(update_time)) => Some(dto::Precondition {
+            update_time: Some(format_timestamp(update_time)),
+            ..Default::default()
+        }),
+        (Some(exists), None) => Some(dto::Precondition {
+            exists: Some(exists),
+            ..Default::default()
+        }),
+        (None, None) => None,
+    }
+}
+
+/// The url query parameters for the given precondition
+pub(crate) fn precondition_params(precondition: &Option<dto::Precondition>) -> Vec<(&'static str, String)> {
+    let mut params = Vec::new();
+    if let Some(precondition) = precondition {
+        if let Some(exists) = precondition.exists {
+            params.push(("currentDocument.exists", exists.to_string()));
+        }
+        if let Some(update_time) = precondition.update_time.as_ref() {
+            params.push(("currentDocument.updateTime", update_time.clone()));
+        }
+    }
+    params
 }
 
 ///
@@ -28,6 +105,14 @@ pub struct WriteOptions {
 /// If a document_id is given, the document will be created if it does not yet exist.
 /// Except if the "merge" option (see [`WriteOptions::merge`]) is set.
 ///
+/// The write fails with [`FirebaseError::PreconditionFailed`] if the preconditions of the options
+/// ([`WriteOptions::exists`], [`WriteOptions::last_update_time`]) are not met.
+/// Preconditions require a document_id.
+///
+/// Documents with field transforms (see [`FieldValue`]) are written with a commit request.
+/// In that case the returned [`WriteResult::create_time`] is only known for documents that are created,
+/// like with [`create`] or without a document_id, and None otherwise.
+///
 /// Example:
 ///```no_run
 ///use firestore_db_and_auth::{Credentials, ServiceSession, documents, errors::Result, FirebaseAuthBearer};
@@ -57,7 +142,7 @@ pub struct WriteOptions {
 /// /// Either via Option<> or by not having the fields in the structure, see DemoPartialDTO.
 /// async fn write_partial(session: &impl FirebaseAuthBearer) -> Result<()> {
 ///    let obj = DemoPartialDTO { a_string: None, an_int: 16 };
-///    let result = documents::write(session, "tests", Some("service_test"), &obj, documents::WriteOptions{merge:true}).await?;
+///    let result = documents::write(session, "tests", Some("service_test"), &obj, documents::WriteOptions{merge:true, ..Default::default()}).await?;
 ///    println!("id: {}, created: {}, updated: {}", result.document_id, result.create_time.unwrap(), result.update_time.unwrap());
 ///    Ok(())
 /// }
@@ -81,7 +166,7 @@ pub struct WriteOptions {
 /// * 'options' Write options
 pub async fn write<T>(
     auth: &impl FirebaseAuthBearer,
-    path: &str,
+    path: impl AsRef<str>,
     document_id: Option<impl AsRef<str>>,
     document: &T,
     options: WriteOptions,
@@ -89,38 +174,206 @@ pub async fn write<T>(
 where
     T: Serialize,
 {
-    let mut url = match document_id.as_ref() {
-        Some(document_id) => firebase_url_extended(auth.project_id(), path, document_id.as_ref()),
-        None => firebase_url(auth.project_id(), path),
+    if document_id.is_none() && (options.exists.is_some() || options.last_update_time.is_some()) {
+        return Err(FirebaseError::Generic("Write preconditions require a document id"));
+    }
+
+    let write = lifted_document_write(pod_to_document_with_transforms(&document)?, &options)?;
+    send_write(
+        auth,
+        path.as_ref(),
+        document_id.as_ref().map(|id| id.as_ref()),
+        write,
+        false,
+    )
+    .await
+}
+
+///
+/// Creates a new document. Fails with [`FirebaseError::AlreadyExists`] if the document already exists.
+///
+/// If no document_id is given, Firestore will generate an ID. Check the [`WriteResult`] return value.
+///
+/// Example:
+/// ```no_run
+/// use serde::{Serialize, Deserialize};
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct DemoDTO { a_string: String, an_int: u32, }
+///
+/// use firestore_db_and_auth::{documents, errors::FirebaseError};
+/// # use firestore_db_and_auth::{credentials::Credentials, ServiceSession, errors::Result};
+/// # use firestore_db_and_auth::credentials::doctest_credentials;
+/// # tokio_test::block_on(async {
+/// # let session = ServiceSession::new(doctest_credentials().await).await.unwrap();
+///
+/// let obj = DemoDTO { a_string: "abc".to_owned(), an_int: 12 };
+/// match documents::create(&session, "tests", Some("create_test"), &obj).await {
+///     Ok(result) => println!("Created at {:?}", result.create_time),
+///     Err(FirebaseError::AlreadyExists(_, _)) => println!("Already exists"),
+///     Err(e) => panic!("{}", e),
+/// }
+/// # })
+/// ```
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'path' The document path / collection; For example "my_collection" or "a/nested/collection"
+/// * 'document_id' The document id. Make sure that you do not include the document id in the path argument.
+/// * 'document' The document
+pub async fn create<T>(
+    auth: &impl FirebaseAuthBearer,
+    path: impl AsRef<str>,
+    document_id: Option<&str>,
+    document: &T,
+) -> Result<WriteResult>
+where
+    T: Serialize,
+{
+    send_write(auth, path.as_ref(), document_id, create_write(document)?, true).await
+}
+
+/// The write operation of [`create`]
+fn create_write<T: Serialize>(document: &T) -> Result<dto::Write> {
+    lifted_document_write(pod_to_document_with_transforms(document)?, &WriteOptions::default())
+}
+
+///
+/// Writes a document and overwrites all fields of an existing document.
+/// The document is created if it does not exist yet.
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'path' The document path / collection; For example "my_collection" or "a/nested/collection"
+/// * 'document_id' The document id. Make sure that you do not include the document id in the path argument.
+/// * 'document' The document
+pub async fn set<T>(
+    auth: &impl FirebaseAuthBearer,
+    path: impl AsRef<str>,
+    document_id: &str,
+    document: &T,
+) -> Result<WriteResult>
+where
+    T: Serialize,
+{
+    send_write(auth, path.as_ref(), Some(document_id), set_write(document)?, false).await
+}
+
+/// The write operation of [`set`]
+fn set_write<T: Serialize>(document: &T) -> Result<dto::Write> {
+    lifted_document_write(pod_to_document_with_transforms(document)?, &WriteOptions::default())
+}
+
+///
+/// Updates the top-level fields of the given document in an existing document. Other fields are kept.
+/// Fails if the document does not exist.
+///
+/// This is the same as [`write`] with [`WriteOptions::merge`] set.
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'path' The document path / collection; For example "my_collection" or "a/nested/collection"
+/// * 'document_id' The document id. Make sure that you do not include the document id in the path argument.
+/// * 'document' The document with the fields to update
+pub async fn update<T>(
+    auth: &impl FirebaseAuthBearer,
+    path: impl AsRef<str>,
+    document_id: &str,
+    document: &T,
+) -> Result<WriteResult>
+where
+    T: Serialize,
+{
+    send_write(auth, path.as_ref(), Some(document_id), update_write(document)?, false).await
+}
+
+/// The write operation of [`update`]
+fn update_write<T: Serialize>(document: &T) -> Result<dto::Write> {
+    let options = WriteOptions {
+        merge: true,
+        ..Default::default()
     };
+    lifted_document_write(pod_to_document_with_transforms(document)?, &options)
+}
+
+///
+/// Merges the given document into a document. All fields, including fields of nested maps,
+/// that are not part of the given document are kept. The document is created if it does not exist yet.
+///
+/// ## Arguments
+/// * 'auth' The authentication token
+/// * 'path' The document path / collection; For example "my_collection" or "a/nested/collection"
+/// * 'document_id' The document id. Make sure that you do not include the document id in the path argument.
+/// * 'document' The document with the fields to merge
+pub async fn set_merge<T>(
+    auth: &impl FirebaseAuthBearer,
+    path: impl AsRef<str>,
+    document_id: &str,
+    document: &T,
+) -> Result<WriteResult>
+where
+    T: Serialize,
+{
+    send_write(
+        auth,
+        path.as_ref(),
+        Some(document_id),
+        set_merge_write(document)?,
+        false,
+    )
+    .await
+}
 
-    let firebase_document = pod_to_document(&document)?;
+/// The write operation of [`set_merge`]
+fn set_merge_write<T: Serialize>(document: &T) -> Result<dto::Write> {
+    let options = WriteOptions {
+        merge: true,
+        deep_merge: true,
+        ..Default::default()
+    };
+    let mut write = lifted_document_write(pod_to_document_with_transforms(document)?, &options)?;
+    // Unlike a merge write, this is an upsert
+    write.current_document = None;
+    Ok(write)
+}
 
-    if options.merge && firebase_document.fields.is_some() {
-        let fields = firebase_document.fields.as_ref().unwrap().keys().join(",");
-        url = format!("{}?currentDocument.exists=true&updateMask.fieldPaths={}", url, fields);
+/// Sends the given write operation for a single document. With `create` set,
+/// the write fails if the document already exists.
+async fn send_write(
+    auth: &impl FirebaseAuthBearer,
+    path: &str,
+    document_id: Option<&str>,
+    mut write: dto::Write,
+    create: bool,
+) -> Result<WriteResult> {
+    let collection = CollectionRef::new(path)?;
+    if let Some(document_id) = document_id {
+        collection.doc(document_id)?;
     }
+    let path = collection.as_ref();
 
-    let builder = if document_id.is_some() {
-        auth.client().patch(&url)
-    } else {
-        auth.client().post(&url)
-    };
+    // Field transforms and empty update masks can only be expressed with a commit request
+    let empty_mask = matches!(write.update_mask.as_ref(), Some(mask) if mask.field_paths.is_empty());
+    if write.update_transforms.is_some() || empty_mask {
+        // Documents with a generated id are created, like with a POST request
+        if create || document_id.is_none() {
+            write.current_document = precondition(Some(false), None);
+        }
+        let document_id = match document_id {
+            Some(document_id) => document_id.to_owned(),
+            None => auto_id()?,
+        };
+        return commit_write(auth, path, document_id, write).await;
+    }
 
-    let resp = builder
+    let requires_existence = requires_existence(&write.current_document);
+    let resp = write_request(auth, path, document_id, write, create)
         .bearer_auth(auth.access_token().await.to_owned())
-        .json(&firebase_document)
         .send()
         .await?;
 
-    let resp = extract_google_api_error_async(resp, || {
-        document_id
-            .as_ref()
-            .and_then(|f| Some(f.as_ref().to_owned()))
-            .or(Some(String::new()))
-            .unwrap()
-    })
-    .await?;
+    let resp =
+        extract_google_write_error_async(resp, requires_existence, || document_id.unwrap_or_default().to_owned())
+            .await?;
 
     let result_document: dto::Document = resp.json().await?;
     let document_id = Path::new(&result_document.name)
@@ -130,26 +383,364 @@ where
         .ok_or_else(|| FirebaseError::Generic("No valid unicode in 'name' field"))?
         .to_owned();
 
-    let create_time = match result_document.create_time {
-        Some(f) => Some(
-            chrono::DateTime::parse_from_rfc3339(&f)
-                .map_err(|_| FirebaseError::Generic("Failed to parse rfc3339 date from 'create_time' field"))?
-                .with_timezone(&chrono::Utc),
-        ),
-        None => None,
-    };
-    let update_time = match result_document.update_time {
-        Some(f) => Some(
-            chrono::DateTime::parse_from_rfc3339(&f)
-                .map_err(|_| FirebaseError::Generic("Failed to parse rfc3339 date from 'update_time' field"))?
-                .with_timezone(&chrono::Utc),
-        ),
-        None => None,
-    };
+    let create_time = parse_timestamp(
+        &result_document.create_time,
+        "Failed to parse rfc3339 date from 'create_time' field",
+    )?;
+    let update_time = parse_timestamp(
+        &result_document.update_time,
+        "Failed to parse rfc3339 date from 'update_time' field",
+    )?;
 
     Ok(WriteResult {
         document_id,
         create_time,
         update_time,
+        transform_results: HashMap::new(),
+    })
+}
+
+/// Creates the PATCH or POST request for the given write operation of a single document
+fn write_request(
+    auth: &impl FirebaseAuthBearer,
+    path: &str,
+    document_id: Option<&str>,
+    mut write: dto::Write,
+    create: bool,
+) -> reqwest::RequestBuilder {
+    let mut params: Vec<(&str, String)> = write
+        .update_mask
+        .iter()
+        .flat_map(|mask| mask.field_paths.iter())
+        .map(|field_path| ("updateMask.fieldPaths", field_path.clone()))
+        .collect();
+    params.extend(precondition_params(&write.current_document));
+    let firebase_document = write.update.take().unwrap_or_default();
+
+    let builder = match (document_id, create) {
+        (Some(document_id), false) => auth.client().patch(firebase_url_extended(auth, path, document_id)),
+        (Some(document_id), true) => {
+            params.push(("documentId", document_id.to_owned()));
+            auth.client().post(firebase_url(auth, path))
+        }
+        (None, _) => auth.client().post(firebase_url(auth, path)),
+    };
+    builder.query(&params).json(&firebase_document)
+}
+
+/// Writes the given write operation with a commit request
+async fn commit_write(
+    auth: &impl FirebaseAuthBearer,
+    path: &str,
+    document_id: String,
+    mut write: dto::Write,
+) -> Result<WriteResult> {
+    if let Some(document) = write.update.as_mut() {
+        document.name = document_name(auth, path, &document_id)?;
+    }
+    let transforms = write.update_transforms.clone();
+    let is_create = matches!(
+        write.current_document,
+        Some(dto::Precondition {
+            exists: Some(false),
+            ..
+        })
+    );
+
+    let response = commit(auth, vec![write], None).await?;
+    let write_result = response
+        .write_results
+        .and_then(|mut results| results.pop())
+        .ok_or(FirebaseError::Generic("No write result in the commit response"))?;
+    let update_time = parse_timestamp(
+        &write_result.update_time,
+        "Failed to parse rfc3339 date from 'update_time' field",
+    )?;
+
+    Ok(WriteResult {
+        // A document that did not exist before has been created with this write
+        create_time: update_time.filter(|_| is_create),
+        update_time,
+        transform_results: transform_results(&transforms, write_result.transform_results),
+        document_id,
+    })
+}
+
+/// Maps the server values of field transforms to the field paths of the transforms
+fn transform_results(
+    transforms: &Option<Vec<dto::FieldTransform>>,
+    results: Option<Vec<dto::Value>>,
+) -> HashMap<String, serde_json::Value> {
+    transforms
+        .iter()
+        .flatten()
+        .zip(results.iter().flatten())
+        .filter_map(|(transform, value)| Some((transform.field_path.clone()?, firebase_value_to_serde_value(value))))
+        .collect()
+}
+
+/// Generates a random document id like Firestore does for documents without an id
+fn auto_id() -> Result<String> {
+    use ring::rand::SecureRandom;
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+    let mut bytes = [0u8; 20];
+    ring::rand::SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| FirebaseError::Generic("Failed to generate a random document id"))?;
+    Ok(bytes.iter().map(|b| CHARS[*b as usize % CHARS.len()] as char).collect())
+}
+
+/// Creates a write operation for the document with the given name, to be used for a transaction.
+/// The given options are applied like for [`write`].
+pub(crate) fn document_write<T>(document_name: String, document: &T, options: &WriteOptions) -> Result<dto::Write>
+where
+    T: Serialize,
+{
+    let mut lifted = pod_to_document_with_transforms(document)?;
+    lifted.document.name = document_name;
+    lifted_document_write(lifted, options)
+}
+
+/// Creates a write operation for the given document, with the field values of the document and the options
+fn lifted_document_write(mut lifted: LiftedDocument, options: &WriteOptions) -> Result<dto::Write> {
+    for (field_path, field_value) in &options.transforms {
+        let field_path = field_path.parse::<FieldPath>()?.to_string();
+        match field_value.to_field_transform(field_path.clone()) {
+            Some(transform) => lifted.transforms.push(transform),
+            None => lifted.deleted_fields.push(field_path),
+        }
+    }
+
+    Ok(dto::Write {
+        current_document: options.precondition(),
+        update_mask: update_mask(&lifted, options)?.map(|field_paths| dto::DocumentMask { field_paths }),
+        update_transforms: match lifted.transforms.is_empty() {
+            true => None,
+            false => Some(lifted.transforms),
+        },
+        update: Some(lifted.document),
+        ..Default::default()
     })
 }
+
+/// The escaped field paths of the update mask for the given document and options.
+/// Deleted fields are always part of the mask, so that they are removed from the target document.
+fn update_mask(lifted: &LiftedDocument, options: &WriteOptions) -> Result<Option<Vec<String>>> {
+    let mut field_paths = match (options.update_mask.as_ref(), options.merge) {
+        (Some(update_mask), _) => update_mask
+            .iter()
+            .map(|field_path| Ok(field_path.parse::<FieldPath>()?.to_string()))
+            .collect::<Result<Vec<String>>>()?,
+        (None, true) => match lifted.document.fields.as_ref() {
+            Some(fields) => document_field_paths(fields, options.deep_merge),
+            None => Vec::new(),
+        },
+        (None, false) if lifted.deleted_fields.is_empty() => return Ok(None),
+        (None, false) => {
+            return Err(FirebaseError::Generic(
+                "FieldValue::delete() requires a merge write or an update mask",
+            ))
+        }
+    };
+    for deleted_field in &lifted.deleted_fields {
+        if !field_paths.contains(deleted_field) {
+            field_paths.push(deleted_field.clone());
+        }
+    }
+    Ok(Some(field_paths))
+}
+
+#[test]
+fn document_write_merge() {
+    #[derive(Serialize)]
+    struct Partial {
+        an_int: u32,
+    }
+
+    let write = document_write(
+        "projects/p/databases/(default)/documents/tests/a".to_owned(),
+        &Partial { an_int: 12 },
+        &WriteOptions {
+            merge: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let json = serde_json::to_value(&write).unwrap();
+    assert_eq!(json["updateMask"]["fieldPaths"], serde_json::json!(["an_int"]));
+    assert_eq!(json["currentDocument"]["exists"], true);
+    assert_eq!(
+        json["update"]["name"],
+        "projects/p/databases/(default)/documents/tests/a"
+    );
+
+    let write = document_write(String::new(), &Partial { an_int: 12 }, &WriteOptions::default()).unwrap();
+    assert!(write.update_mask.is_none());
+    assert!(write.current_document.is_none());
+    assert!(write.update_transforms.is_none());
+
+    let options = WriteOptions {
+        transforms: vec![("a_map.count".to_owned(), FieldValue::increment(1))],
+        ..Default::default()
+    };
+    let write = document_write(String::new(), &Partial { an_int: 12 }, &options).unwrap();
+    let json = serde_json::to_value(&write).unwrap();
+    assert_eq!(
+        json["updateTransforms"],
+        serde_json::json!([{"fieldPath": "a_map.count", "increment": {"integerValue": "1"}}])
+    );
+}
+
+#[test]
+fn document_write_request_shapes() {
+    #[derive(Serialize)]
+    struct Partial {
+        a_map: HashMap<&'static str, u32>,
+    }
+
+    fn request(write: dto::Write, document_id: Option<&str>, create: bool) -> (reqwest::Request, serde_json::Value) {
+        let auth = super::tests::TestAuth::new();
+        let request = write_request(&auth, "tests", document_id, write, create)
+            .build()
+            .unwrap();
+        let body = serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+        (request, body)
+    }
+    fn query(request: &reqwest::Request) -> Vec<(String, String)> {
+        request.url().query_pairs().into_owned().collect()
+    }
+
+    let document = Partial {
+        a_map: [("count", 1)].into_iter().collect(),
+    };
+
+    let (create, body) = request(create_write(&document).unwrap(), Some("a"), true);
+    assert_eq!(create.method(), reqwest::Method::POST);
+    assert!(create.url().path().ends_with("/documents/tests"));
+    assert_eq!(query(&create), [("documentId".to_owned(), "a".to_owned())]);
+    assert_eq!(
+        body["fields"]["a_map"]["mapValue"]["fields"]["count"]["integerValue"],
+        "1"
+    );
+
+    let (set, _) = request(set_write(&document).unwrap(), Some("a"), false);
+    assert_eq!(set.method(), reqwest::Method::PATCH);
+    assert!(set.url().path().ends_with("/documents/tests/a"));
+    // Without an update mask all fields are replaced
+    assert!(query(&set).is_empty());
+
+    let (update, _) = request(update_write(&document).unwrap(), Some("a"), false);
+    assert_eq!(update.method(), reqwest::Method::PATCH);
+    assert_eq!(
+        query(&update),
+        [
+            ("updateMask.fieldPaths".to_owned(), "a_map".to_owned()),
+            ("currentDocument.exists".to_owned(), "true".to_owned()),
+        ]
+    );
+
+    let (set_merge, _) = request(set_merge_write(&document).unwrap(), Some("a"), false);
+    assert_eq!(set_merge.method(), reqwest::Method::PATCH);
+    assert_eq!(
+        query(&set_merge),
+        [("updateMask.fieldPaths".to_owned(), "a_map.count".to_owned())]
+    );
+}
+
+#[test]
+fn document_write_merge_transforms_only() {
+    let document = serde_json::json!({
+        "stats": {"visits": FieldValue::increment(1), "daily": {"max": FieldValue::maximum(3)}},
+        "empty": {},
+        "name": "x",
+    });
+    for deep_merge in [false, true] {
+        let options = WriteOptions {
+            merge: true,
+            deep_merge,
+            ..Default::default()
+        };
+        let write = document_write(String::new(), &document, &options).unwrap();
+        // Maps that only contain field transforms must not replace their siblings
+        assert_eq!(write.update_mask.unwrap().field_paths, ["empty", "name"]);
+        let fields = write.update.unwrap().fields.unwrap();
+        assert!(!fields.contains_key("stats"));
+        let transforms: Vec<String> = write
+            .update_transforms
+            .unwrap()
+            .into_iter()
+            .filter_map(|transform| transform.field_path)
+            .collect();
+        assert_eq!(transforms.len(), 2);
+        assert!(transforms.contains(&"stats.visits".to_owned()));
+        assert!(transforms.contains(&"stats.daily.max".to_owned()));
+    }
+}
+
+#[test]
+fn write_options_precondition() {
+    let update_time = chrono::DateTime::parse_from_rfc3339("2020-04-28T14:52:51.250511Z")
+        .unwrap()
+        .into();
+    let options = WriteOptions {
+        merge: true,
+        last_update_time: Some(update_time),
+        ..Default::default()
+    };
+    assert_eq!(
+        precondition_params(&options.precondition()),
+        vec![("currentDocument.updateTime", "2020-04-28T14:52:51.250511Z".to_owned())]
+    );
+
+    let options = WriteOptions {
+        exists: Some(false),
+        ..Default::default()
+    };
+    assert_eq!(
+        precondition_params(&options.precondition()),
+        vec![("currentDocument.exists", "false".to_owned())]
+    );
+    assert!(WriteOptions::default().precondition().is_none());
+}
+
+#[test]
+fn write_options_update_mask() {
+    let document = serde_json::json!({
+        "a_map": {"first.name": "x", "count": 1},
+        "removed": FieldValue::delete(),
+    });
+
+    let options = WriteOptions {
+        merge: true,
+        ..Default::default()
+    };
+    let write = document_write(String::new(), &document, &options).unwrap();
+    assert_eq!(write.update_mask.unwrap().field_paths, ["a_map", "removed"]);
+    assert!(write.update.unwrap().fields.unwrap().get("removed").is_none());
+
+    let options = WriteOptions {
+        merge: true,
+        deep_merge: true,
+        ..Default::default()
+    };
+    let write = document_write(String::new(), &document, &options).unwrap();
+    assert_eq!(
+        write.update_mask.unwrap().field_paths,
+        ["a_map.`first.name`", "a_map.count", "removed"]
+    );
+
+    let options = WriteOptions {
+        update_mask: Some(vec!["a_map.count".to_owned(), "other".to_owned()]),
+        transforms: vec![("`a b`".to_owned(), FieldValue::delete())],
+        ..Default::default()
+    };
+    let write = document_write(String::new(), &document, &options).unwrap();
+    assert_eq!(
+        write.update_mask.unwrap().field_paths,
+        ["a_map.count", "other", "removed", "`a b`"]
+    );
+    assert!(write.current_document.is_none());
+
+    assert!(document_write(String::new(), &document, &WriteOptions::default()).is_err());
+}
@@ -47,24 +47,55 @@ pub struct Write {
     #[serde(rename = "updateMask")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub update_mask: Option<DocumentMask>,
+    #[serde(rename = "updateTransforms")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_transforms: Option<Vec<FieldTransform>>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub enum FieldOperator {
-    OPERATOR_UNSPECIFIED,  //	Unspecified. This value must not be used.
+    #[default]
+    OPERATOR_UNSPECIFIED, //	Unspecified. This value must not be used.
     LESS_THAN,             //	Less than. Requires that the field come first in orderBy.
     LESS_THAN_OR_EQUAL,    //	Less than or equal. Requires that the field come first in orderBy.
     GREATER_THAN,          //	Greater than. Requires that the field come first in orderBy.
     GREATER_THAN_OR_EQUAL, //	Greater than or equal. Requires that the field come first in orderBy.
     EQUAL,                 //	Equal.
+    NOT_EQUAL,             //	Not equal. Requires that the field come first in orderBy.
     ARRAY_CONTAINS,        //	Contains. Requires that the field is an array.
+    IN,                    //	The field is equal to at least one value in the given array.
+    ARRAY_CONTAINS_ANY,    //	Contains any value of the given array. Requires that the field is an array.
+    NOT_IN,                //	The field is not in the given array. Requires that the field come first in orderBy.
 }
 
-impl Default for FieldOperator {
-    fn default() -> Self {
-        FieldOperator::OPERATOR_UNSPECIFIED
-    }
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+pub enum UnaryOperator {
+    #[default]
+    OPERATOR_UNSPECIFIED, //	Unspecified. This value must not be used.
+    IS_NAN,      //	The given field is equal to NaN.
+    IS_NULL,     //	The given field is equal to NULL.
+    IS_NOT_NAN,  //	The given field is not equal to NaN.
+    IS_NOT_NULL, //	The given field is not equal to NULL.
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+pub enum CompositeOperator {
+    #[default]
+    OPERATOR_UNSPECIFIED, //	Unspecified. This value must not be used.
+    AND, //	Documents are required to satisfy all of the combined filters.
+    OR,  //	Documents are required to satisfy at least one of the combined filters.
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+pub enum Direction {
+    #[default]
+    DIRECTION_UNSPECIFIED, //	Unspecified.
+    ASCENDING,  //	Ascending.
+    DESCENDING, //	Descending.
 }
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
@@ -114,15 +145,18 @@ pub struct BatchGetDocumentsResponse {
 pub struct Status {
     pub message: Option<String>,
     pub code: Option<i32>,
-    pub details: Option<Vec<HashMap<String, String>>>,
+    pub details: Option<Vec<HashMap<String, serde_json::Value>>>,
 }
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct ListenRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub labels: Option<HashMap<String, String>>,
     #[serde(rename = "addTarget")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub add_target: Option<Target>,
     #[serde(rename = "removeTarget")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub remove_target: Option<i32>,
 }
 
@@ -149,7 +183,7 @@ pub struct FieldReference {
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct UnaryFilter {
     pub field: FieldReference,
-    pub op: String,
+    pub op: UnaryOperator,
 }
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
@@ -166,7 +200,7 @@ pub struct DocumentMask {
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct CompositeFilter {
     pub filters: Vec<Filter>,
-    pub op: String,
+    pub op: CompositeOperator,
 }
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
@@ -202,9 +236,14 @@ pub struct WriteResponse {
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct ListCollectionIdsRequest {
     #[serde(rename = "pageToken")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub page_token: Option<String>,
     #[serde(rename = "pageSize")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub page_size: Option<i32>,
+    #[serde(rename = "readTime")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_time: Option<String>,
 }
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
@@ -231,8 +270,10 @@ pub struct MapValue {
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct TransactionOptions {
     #[serde(rename = "readWrite")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub read_write: Option<ReadWrite>,
     #[serde(rename = "readOnly")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub read_only: Option<ReadOnly>,
 }
 
@@ -246,14 +287,20 @@ pub struct CommitResponse {
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Target {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub documents: Option<DocumentsTarget>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub once: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub query: Option<QueryTarget>,
     #[serde(rename = "resumeToken")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub resume_token: Option<String>,
     #[serde(rename = "targetId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub target_id: Option<i32>,
     #[serde(rename = "readTime")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub read_time: Option<String>,
 }
 
@@ -298,6 +345,7 @@ pub struct Value {
 
     #[serde(rename = "doubleValue")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, with = "double_value")]
     pub double_value: Option<f64>,
 
     #[serde(rename = "mapValue")]
@@ -359,6 +407,7 @@ pub struct StructuredQuery {
     #[serde(rename = "endAt")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub end_at: Option<Cursor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub limit: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub offset: Option<i32>,
@@ -374,13 +423,23 @@ pub struct StructuredQuery {
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct FieldTransform {
     #[serde(rename = "fieldPath")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub field_path: Option<String>,
     #[serde(rename = "appendMissingElements")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub append_missing_elements: Option<ArrayValue>,
     #[serde(rename = "setToServerValue")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub set_to_server_value: Option<String>,
     #[serde(rename = "removeAllFromArray")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub remove_all_from_array: Option<ArrayValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub increment: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<Value>,
 }
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
@@ -408,7 +467,7 @@ pub struct Order {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub field: Option<FieldReference>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub direction: Option<String>,
+    pub direction: Option<Direction>,
 }
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
@@ -434,6 +493,73 @@ pub struct RunQueryResponse {
     pub read_time: Option<String>,
 }
 
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct RunAggregationQueryRequest {
+    #[serde(rename = "newTransaction")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_transaction: Option<TransactionOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<String>,
+    #[serde(rename = "structuredAggregationQuery")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured_aggregation_query: Option<StructuredAggregationQuery>,
+    #[serde(rename = "readTime")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_time: Option<String>,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct StructuredAggregationQuery {
+    #[serde(rename = "structuredQuery")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured_query: Option<StructuredQuery>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aggregations: Option<Vec<Aggregation>>,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Aggregation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<Count>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sum: Option<Sum>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg: Option<Avg>,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Count {
+    #[serde(rename = "upTo")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub up_to: Option<String>,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Sum {
+    pub field: Option<FieldReference>,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Avg {
+    pub field: Option<FieldReference>,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct RunAggregationQueryResponse {
+    pub result: Option<AggregationResult>,
+    pub transaction: Option<String>,
+    #[serde(rename = "readTime")]
+    pub read_time: Option<String>,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct AggregationResult {
+    #[serde(rename = "aggregateFields")]
+    pub aggregate_fields: Option<HashMap<String, Value>>,
+}
+
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct ListCollectionIdsResponse {
     #[serde(rename = "nextPageToken")]
@@ -448,6 +574,21 @@ pub struct CommitRequest {
     pub transaction: Option<String>,
 }
 
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct BatchWriteRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub writes: Option<Vec<Write>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct BatchWriteResponse {
+    #[serde(rename = "writeResults")]
+    pub write_results: Option<Vec<WriteResult>>,
+    pub status: Option<Vec<Status>>,
+}
+
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Projection {
     pub fields: Option<Vec<FieldReference>>,
@@ -463,6 +604,7 @@ pub struct ListDocumentsResponse {
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct ReadWrite {
     #[serde(rename = "retryTransaction")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub retry_transaction: Option<String>,
 }
 
@@ -507,6 +649,7 @@ pub struct RollbackRequest {
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct ReadOnly {
     #[serde(rename = "readTime")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub read_time: Option<String>,
 }
 
@@ -533,7 +676,9 @@ pub struct WriteResult {
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct QueryTarget {
     #[serde(rename = "structuredQuery")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub structured_query: Option<StructuredQuery>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub parent: Option<String>,
 }
 
@@ -575,6 +720,40 @@ pub struct OAuthResponse {
     pub raw_user_info: String,
 }
 
+/// Doubles are encoded as json numbers, except for NaN and the infinities,
+/// which are encoded as the strings "NaN", "Infinity" and "-Infinity"
+mod double_value {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(v) if v.is_nan() => serializer.serialize_str("NaN"),
+            Some(v) if v.is_infinite() && v.is_sign_positive() => serializer.serialize_str("Infinity"),
+            Some(v) if v.is_infinite() => serializer.serialize_str("-Infinity"),
+            value => value.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Double {
+            Number(f64),
+            String(String),
+        }
+        match Option::<Double>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(Double::Number(v)) => Ok(Some(v)),
+            Some(Double::String(v)) => match v.as_str() {
+                "NaN" => Ok(Some(f64::NAN)),
+                "Infinity" => Ok(Some(f64::INFINITY)),
+                "-Infinity" => Ok(Some(f64::NEG_INFINITY)),
+                _ => Err(de::Error::custom(format!("invalid double value '{}'", v))),
+            },
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -0,0 +1,162 @@
+//! # Firestore and Firebase Auth emulator support
+//!
+//! The local emulators of the Firebase CLI allow testing without a real Firebase project.
+//! Credentials pick up the emulator hosts from the `FIRESTORE_EMULATOR_HOST` and `FIREBASE_AUTH_EMULATOR_HOST`
+//! environment variables, or from [`crate::Credentials::with_emulator`] and [`crate::Credentials::for_emulator`].
+//!
+//! In emulator mode:
+//! * Service account sessions use the emulator's `owner` bearer token, which bypasses security rules.
+//! * Custom tokens are not signed and access tokens are not verified.
+//! * No JWKS is downloaded.
+
+use super::errors::{extract_google_api_error_async, FirebaseError, Result};
+use super::FirebaseAuthBearer;
+
+/// The environment variable with the "host:port" of the Firestore emulator
+pub const FIRESTORE_EMULATOR_HOST: &str = "FIRESTORE_EMULATOR_HOST";
+/// The environment variable with the "host:port" of the Firebase Auth emulator
+pub const FIREBASE_AUTH_EMULATOR_HOST: &str = "FIREBASE_AUTH_EMULATOR_HOST";
+
+/// The bearer token that grants full access to the Firestore emulator
+pub(crate) const EMULATOR_OWNER_TOKEN: &str = "owner";
+
+/// The hosts of the local Firestore and Firebase Auth emulators, like "localhost:8080".
+/// APIs without a configured emulator host are accessed as usual.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Emulator {
+    /// The "host:port" of the Firestore emulator
+    pub firestore_host: Option<String>,
+    /// The "host:port" of the Firebase Auth emulator
+    pub auth_host: Option<String>,
+}
+
+impl Emulator {
+    /// Reads the emulator hosts from the `FIRESTORE_EMULATOR_HOST` and `FIREBASE_AUTH_EMULATOR_HOST`
+    /// environment variables. Returns None if neither is set.
+    pub fn from_env() -> Option<Emulator> {
+        let host = |name| std::env::var(name).ok().filter(|host: &String| !host.is_empty());
+        match (host(FIRESTORE_EMULATOR_HOST), host(FIREBASE_AUTH_EMULATOR_HOST)) {
+            (None, None) => None,
+            (firestore_host, auth_host) => Some(Emulator {
+                firestore_host,
+                auth_host,
+            }),
+        }
+    }
+}
+
+/// The base url of the Firestore REST API, or of the Firestore emulator if one is configured
+pub(crate) fn firestore_url(emulator: Option<&Emulator>) -> String {
+    match emulator.and_then(|emulator| emulator.firestore_host.as_deref()) {
+        Some(host) => format!("http://{}/v1", host),
+        None => "https://firestore.googleapis.com/v1".to_owned(),
+    }
+}
+
+/// Rewrites the url of a Google Auth API, like "https://identitytoolkit.googleapis.com/v1/accounts:lookup",
+/// to the Firebase Auth emulator if one is configured
+pub(crate) fn auth_url(emulator: Option<&Emulator>, url: String) -> String {
+    match emulator.and_then(|emulator| emulator.auth_host.as_deref()) {
+        Some(host) => format!("http://{}/{}", host, url.trim_start_matches("https://")),
+        None => url,
+    }
+}
+
+/// Deletes all documents of the database of the given session from the Firestore emulator
+pub async fn clear_firestore(auth: &impl FirebaseAuthBearer) -> Result<()> {
+    let host = auth
+        .emulator()
+        .and_then(|emulator| emulator.firestore_host.as_deref())
+        .ok_or(FirebaseError::Generic("No Firestore emulator configured"))?;
+    let url = format!(
+        "http://{}/emulator/v1/projects/{}/databases/{}/documents",
+        host,
+        auth.project_id(),
+        auth.database_id()
+    );
+    clear(auth, &url).await
+}
+
+/// Deletes all user accounts of the project of the given session from the Firebase Auth emulator
+pub async fn clear_auth(auth: &impl FirebaseAuthBearer) -> Result<()> {
+    let host = auth
+        .emulator()
+        .and_then(|emulator| emulator.auth_host.as_deref())
+        .ok_or(FirebaseError::Generic("No Firebase Auth emulator configured"))?;
+    let url = format!("http://{}/emulator/v1/projects/{}/accounts", host, auth.project_id());
+    clear(auth, &url).await
+}
+
+async fn clear(auth: &impl FirebaseAuthBearer, url: &str) -> Result<()> {
+    let resp = auth
+        .client()
+        .delete(url)
+        .bearer_auth(EMULATOR_OWNER_TOKEN)
+        .send()
+        .await?;
+    extract_google_api_error_async(resp, || url.to_owned()).await?;
+    Ok(())
+}
+
+#[test]
+fn emulator_urls() {
+    let emulator = Emulator {
+        firestore_host: Some("localhost:8080".to_owned()),
+        auth_host: Some("localhost:9099".to_owned()),
+    };
+    assert_eq!(firestore_url(Some(&emulator)), "http://localhost:8080/v1");
+    assert_eq!(firestore_url(None), "https://firestore.googleapis.com/v1");
+    assert_eq!(
+        auth_url(
+            Some(&emulator),
+            "https://identitytoolkit.googleapis.com/v1/accounts:lookup?key=k".to_owned()
+        ),
+        "http://localhost:9099/identitytoolkit.googleapis.com/v1/accounts:lookup?key=k"
+    );
+    let firestore_only = Emulator {
+        auth_host: None,
+        ..emulator
+    };
+    assert_eq!(
+        auth_url(
+            Some(&firestore_only),
+            "https://securetoken.googleapis.com/v1/token".to_owned()
+        ),
+        "https://securetoken.googleapis.com/v1/token"
+    );
+}
+
+#[tokio::test]
+async fn emulator_sessions() {
+    use crate::jwt::{create_jwt, encode_unsigned};
+    use crate::{Credentials, ServiceSession, UserSession};
+
+    let credentials = Credentials::for_emulator(
+        "demo-project",
+        Emulator {
+            firestore_host: Some("localhost:8080".to_owned()),
+            auth_host: Some("localhost:9099".to_owned()),
+        },
+    );
+    credentials.verify().await.unwrap();
+
+    let session = ServiceSession::new(credentials.clone()).await.unwrap();
+    assert_eq!(session.access_token().await, EMULATOR_OWNER_TOKEN);
+    assert!(session.emulator().is_some());
+
+    let scope: Option<std::slice::Iter<String>> = None;
+    let jwt = create_jwt(
+        &credentials,
+        scope,
+        chrono::Duration::hours(1),
+        None,
+        Some("uid".to_owned()),
+        "demo-project",
+    )
+    .unwrap();
+    let session = UserSession::by_access_token(&credentials, &encode_unsigned(&jwt).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(session.project_id(), "demo-project");
+    assert_eq!(session.emulator(), credentials.emulator.as_ref());
+}
@@ -27,6 +27,21 @@ pub enum FirebaseError {
     /// If the APIError happens on a user_* method, the user id will be set as context.
     /// For example: 400, CREDENTIAL_TOO_OLD_LOGIN_AGAIN
     APIError(usize, String, String),
+    /// The Firestore API aborted the operation, usually because of a concurrency conflict.
+    /// For example a transaction that was invalidated by a concurrent write.
+    /// Contains the message and a context like [`FirebaseError::APIError`].
+    /// Operations that fail with this error can be retried.
+    Aborted(String, String),
+    /// A precondition of a write or delete operation, like the last update time of the target document,
+    /// has not been met. Contains the message and a context like [`FirebaseError::APIError`].
+    /// This includes a failed `exists: true` precondition, which Firestore reports as "NOT_FOUND".
+    ///
+    /// "FAILED_PRECONDITION" errors of other operations, like queries that require a missing index,
+    /// are returned as [`FirebaseError::APIError`].
+    PreconditionFailed(String, String),
+    /// A document could not be created, because it already exists.
+    /// Contains the message and a context like [`FirebaseError::APIError`].
+    AlreadyExists(String, String),
     /// An error caused by the http library. This only happens if the http request is badly
     /// formatted (too big, invalid characters) or if the server did strange things
     /// (connection abort, ssl verification error).
@@ -70,6 +85,15 @@ impl std::convert::From<serde_json::Error> for FirebaseError {
     }
 }
 
+impl std::convert::From<crate::firestore_serde::Error> for FirebaseError {
+    fn from(error: crate::firestore_serde::Error) -> Self {
+        FirebaseError::Ser {
+            doc: None,
+            ser: serde::ser::Error::custom(error),
+        }
+    }
+}
+
 impl std::convert::From<biscuit::errors::Error> for FirebaseError {
     fn from(error: biscuit::errors::Error) -> Self {
         FirebaseError::JWT(error)
@@ -95,6 +119,15 @@ impl fmt::Display for FirebaseError {
             FirebaseError::APIError(code, m, context) => {
                 write!(f, "API Error! Code {} - {}. Context: {}", code, m, context)
             }
+            FirebaseError::Aborted(m, context) => {
+                write!(f, "Aborted! {}. Context: {}", m, context)
+            }
+            FirebaseError::PreconditionFailed(m, context) => {
+                write!(f, "Precondition failed! {}. Context: {}", m, context)
+            }
+            FirebaseError::AlreadyExists(m, context) => {
+                write!(f, "Already exists! {}. Context: {}", m, context)
+            }
             FirebaseError::UnexpectedResponse(m, status, text, source) => {
                 writeln!(f, "{} - {}", &m, status)?;
                 writeln!(f, "{}", text)?;
@@ -131,6 +164,9 @@ impl error::Error for FirebaseError {
             FirebaseError::Generic(ref _m) => None,
             FirebaseError::UnexpectedResponse(_, _, _, _) => None,
             FirebaseError::APIError(_, _, _) => None,
+            FirebaseError::Aborted(_, _) => None,
+            FirebaseError::PreconditionFailed(_, _) => None,
+            FirebaseError::AlreadyExists(_, _) => None,
             FirebaseError::Request(ref e) => Some(e),
             FirebaseError::JWT(ref e) => Some(e),
             FirebaseError::JWTValidation(ref e) => Some(e),
@@ -153,6 +189,8 @@ struct GoogleRESTApiError {
 struct GoogleRESTApiErrorInfo {
     pub code: usize,
     pub message: String,
+    /// The canonical error code, for example "ABORTED" or "NOT_FOUND"
+    pub status: Option<String>,
     pub errors: Option<Vec<GoogleRESTApiError>>,
 }
 
@@ -180,6 +218,7 @@ pub(crate) fn extract_google_api_error(
         response.status().clone(),
         response.text()?,
         context,
+        RequestKind::Other,
     ))
 }
 
@@ -202,21 +241,117 @@ pub(crate) async fn extract_google_api_error_async(
         response.status().clone(),
         response.text().await?,
         context,
+        RequestKind::Other,
+    ))
+}
+
+/// Like [`extract_google_api_error_async`], for the responses of write, delete and commit requests.
+/// Only for those, a "FAILED_PRECONDITION" status means that a precondition of a write has not been met
+/// and is returned as [`FirebaseError::PreconditionFailed`]. If `requires_existence` is set, because a write
+/// has an `exists: true` precondition, a "NOT_FOUND" status is a failed precondition as well.
+pub(crate) async fn extract_google_write_error_async(
+    response: reqwest::Response,
+    requires_existence: bool,
+    context: impl Fn() -> String,
+) -> Result<reqwest::Response> {
+    if response.status() == 200 {
+        return Ok(response);
+    }
+
+    Err(extract_google_api_error_intern(
+        response.status(),
+        response.text().await?,
+        context,
+        RequestKind::Write { requires_existence },
     ))
 }
 
+/// The kind of request of an error response, see [`extract_google_write_error_async`]
+#[derive(Clone, Copy)]
+enum RequestKind {
+    Write { requires_existence: bool },
+    Other,
+}
+
 fn extract_google_api_error_intern(
     status: StatusCode,
     http_body: String,
     context: impl Fn() -> String,
+    kind: RequestKind,
 ) -> FirebaseError {
     let google_api_error_wrapper: std::result::Result<GoogleRESTApiErrorWrapper, serde_json::Error> =
         serde_json::from_str(&http_body);
     if let Ok(google_api_error_wrapper) = google_api_error_wrapper {
         if let Some(google_api_error) = google_api_error_wrapper.error {
-            return FirebaseError::APIError(google_api_error.code, google_api_error.message.to_owned(), context());
+            return match (google_api_error.status.as_deref(), kind) {
+                (Some("ABORTED"), _) => FirebaseError::Aborted(google_api_error.message, context()),
+                (Some("FAILED_PRECONDITION"), RequestKind::Write { .. })
+                | (
+                    Some("NOT_FOUND"),
+                    RequestKind::Write {
+                        requires_existence: true,
+                    },
+                ) => FirebaseError::PreconditionFailed(google_api_error.message, context()),
+                (Some("ALREADY_EXISTS"), _) => FirebaseError::AlreadyExists(google_api_error.message, context()),
+                _ => FirebaseError::APIError(google_api_error.code, google_api_error.message, context()),
+            };
         }
     };
 
     FirebaseError::UnexpectedResponse("", status, http_body, context())
 }
+
+#[test]
+fn google_api_error_status() {
+    let write = RequestKind::Write {
+        requires_existence: false,
+    };
+    let body = r#"{"error": {"code": 409, "message": "Transaction lock timeout.", "status": "ABORTED"}}"#;
+    match extract_google_api_error_intern(StatusCode::CONFLICT, body.to_owned(), || "tests/a".to_owned(), write) {
+        FirebaseError::Aborted(message, context) => {
+            assert_eq!(message, "Transaction lock timeout.");
+            assert_eq!(context, "tests/a");
+        }
+        e => panic!("Expected an Aborted error, got {:?}", e),
+    }
+
+    let body = r#"{"error": {"code": 400, "message": "the stored version does not match the required base version", "status": "FAILED_PRECONDITION"}}"#;
+    match extract_google_api_error_intern(StatusCode::BAD_REQUEST, body.to_owned(), || "tests/a".to_owned(), write) {
+        FirebaseError::PreconditionFailed(_, context) => assert_eq!(context, "tests/a"),
+        e => panic!("Expected a PreconditionFailed error, got {:?}", e),
+    }
+
+    let body = r#"{"error": {"code": 409, "message": "Document already exists", "status": "ALREADY_EXISTS"}}"#;
+    match extract_google_api_error_intern(StatusCode::CONFLICT, body.to_owned(), || "tests/a".to_owned(), write) {
+        FirebaseError::AlreadyExists(message, _) => assert_eq!(message, "Document already exists"),
+        e => panic!("Expected an AlreadyExists error, got {:?}", e),
+    }
+
+    let body = r#"{"error": {"code": 404, "message": "No document to update", "status": "NOT_FOUND"}}"#;
+    match extract_google_api_error_intern(StatusCode::NOT_FOUND, body.to_owned(), || "tests/a".to_owned(), write) {
+        FirebaseError::APIError(code, _, _) => assert_eq!(code, 404),
+        e => panic!("Expected an APIError, got {:?}", e),
+    }
+
+    // A missing document is a failed precondition if the write requires it to exist
+    let exists = RequestKind::Write {
+        requires_existence: true,
+    };
+    match extract_google_api_error_intern(StatusCode::NOT_FOUND, body.to_owned(), || "tests/a".to_owned(), exists) {
+        FirebaseError::PreconditionFailed(message, _) => assert_eq!(message, "No document to update"),
+        e => panic!("Expected a PreconditionFailed error, got {:?}", e),
+    }
+
+    // Only writes fail because of preconditions. Queries for example fail like this without an index.
+    let body =
+        r#"{"error": {"code": 400, "message": "The query requires an index.", "status": "FAILED_PRECONDITION"}}"#;
+    match extract_google_api_error_intern(
+        StatusCode::BAD_REQUEST,
+        body.to_owned(),
+        || "tests".to_owned(),
+        RequestKind::Other,
+    ) {
+        FirebaseError::APIError(code, _, _) => assert_eq!(code, 400),
+        e => panic!("Expected an APIError, got {:?}", e),
+    }
+}
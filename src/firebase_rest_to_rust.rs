@@ -8,25 +8,27 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 
+use super::documents::{firebase_value_to_marker, marker_to_firebase_value, FieldPath, FieldValue, FIELD_VALUE_MARKER};
 use super::dto;
 use super::errors::{FirebaseError, Result};
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Wrapper {
-    #[serde(flatten)]
-    extra: HashMap<String, Value>,
-}
+use super::firestore_serde;
 
 use serde_json::{map::Map, Number};
 
 /// Converts a firebase google-rpc-api inspired heavily nested and wrapped response value
 /// of the Firebase REST API into a flattened serde json value.
 ///
+/// Timestamps are converted into rfc3339 strings. Bytes, reference and geo point values are converted into
+/// the serialized form of [`crate::documents::Bytes`], [`crate::documents::DocumentReference`]
+/// and [`crate::documents::GeoPoint`].
+///
 /// This is a low level API. You probably want to use [`crate::documents`] instead.
 ///
 /// This method works recursively!
 pub(crate) fn firebase_value_to_serde_value(v: &dto::Value) -> serde_json::Value {
-    if let Some(timestamp_value) = v.timestamp_value.as_ref() {
+    if let Some(marker) = firebase_value_to_marker(v) {
+        return marker;
+    } else if let Some(timestamp_value) = v.timestamp_value.as_ref() {
         return Value::String(timestamp_value.clone());
     } else if let Some(integer_value) = v.integer_value.as_ref() {
         if let Ok(four) = integer_value.parse::<i64>() {
@@ -63,6 +65,9 @@ pub(crate) fn firebase_value_to_serde_value(v: &dto::Value) -> serde_json::Value
 /// Converts a flat serde json value into a firebase google-rpc-api inspired heavily nested and wrapped type
 /// to be consumed by the Firebase REST API.
 ///
+/// The serialized forms of [`crate::documents::FirestoreTimestamp`], [`crate::documents::GeoPoint`],
+/// [`crate::documents::DocumentReference`] and [`crate::documents::Bytes`] are converted into the respective values.
+///
 /// This is a low level API. You probably want to use [`crate::documents`] instead.
 ///
 /// This method works recursively!
@@ -78,6 +83,9 @@ pub(crate) fn serde_value_to_firebase_value(v: &serde_json::Value) -> dto::Value
             ..Default::default()
         };
     } else if let Some(map_value) = v.as_object() {
+        if let Some(value) = marker_to_firebase_value(map_value) {
+            return value;
+        }
         let mut map: HashMap<String, dto::Value> = HashMap::new();
         for (map_key, map_v) in map_value {
             map.insert(map_key.to_owned(), serde_value_to_firebase_value(&map_v));
@@ -106,12 +114,20 @@ pub(crate) fn serde_value_to_firebase_value(v: &serde_json::Value) -> dto::Value
             ..Default::default()
         };
     }
-    Default::default()
+    dto::Value {
+        null_value: Some("NULL_VALUE".to_owned()),
+        ..Default::default()
+    }
 }
 
 /// Converts a firebase google-rpc-api inspired heavily nested and wrapped response document
 /// of the Firebase REST API into a given custom type.
 ///
+/// Timestamp values are read as rfc3339 strings, unless the target field is a
+/// [`crate::documents::FirestoreTimestamp`]. Untyped targets like `serde_json::Value` therefore
+/// lose the timestamp type, and writing them back stores strings. Use [`crate::documents::DynamicDocument`]
+/// to keep it.
+///
 /// This is a low level API. You probably want to use [`crate::documents`] instead.
 ///
 /// Arguments:
@@ -120,36 +136,18 @@ pub(crate) fn serde_value_to_firebase_value(v: &serde_json::Value) -> dto::Value
 ///
 /// Internals:
 ///
-/// This method uses recursion to decode the given firebase type.
+/// The document fields are deserialized directly with the [`crate::firestore_serde`] deserializer.
 pub fn document_to_pod<T>(document: &dto::Document, input_doc: Option<&Bytes>) -> Result<T>
 where
     for<'de> T: Deserialize<'de>,
 {
-    // The firebase document has a field called "fields" that contain all top-level fields.
-    // We want those to be flattened to our custom data structure. To not reinvent the wheel,
-    // perform the firebase-value to serde-values conversion for all fields first and wrap those
-    // Wrapper struct with a HashMap. Use #[serde(flatten)] on that map.
-    let r = Wrapper {
-        extra: document
-            .fields
-            .as_ref()
-            .unwrap()
-            .iter()
-            .map(|(k, v)| {
-                return (k.to_owned(), firebase_value_to_serde_value(&v));
-            })
-            .collect(),
-    };
-
-    let v = serde_json::to_value(r)?;
-    let r: T = serde_json::from_value(v).map_err(|e| FirebaseError::SerdeVerbose {
+    firestore_serde::from_document(document).map_err(|e| FirebaseError::SerdeVerbose {
         doc: Some(document.name.clone()),
         input_doc: String::from_utf8_lossy(input_doc.unwrap_or(&Bytes::new()))
             .replace("\n", " ")
             .to_string(),
-        ser: e,
-    })?;
-    Ok(r)
+        ser: serde::de::Error::custom(e),
+    })
 }
 
 /// Converts a custom data type into a firebase google-rpc-api inspired heavily nested and wrapped type
@@ -159,16 +157,100 @@ where
 ///
 /// Internals:
 ///
-/// This method uses recursion to decode the given firebase type.
+/// The type is serialized directly with the [`crate::firestore_serde`] serializer.
+///
+/// Returns an error if the type does not serialize to a map or if it contains [`crate::documents::FieldValue`]s. Use [`pod_to_document_with_transforms`]
+/// for those types.
 pub fn pod_to_document<T>(pod: &T) -> Result<dto::Document>
 where
     T: Serialize,
 {
-    let v = serde_json::to_value(pod)?;
-    Ok(dto::Document {
-        fields: serde_value_to_firebase_value(&v).map_value.unwrap().fields,
-        ..Default::default()
-    })
+    let lifted = pod_to_document_with_transforms(pod)?;
+    if !lifted.transforms.is_empty() || !lifted.deleted_fields.is_empty() {
+        return Err(FirebaseError::Generic(
+            "Field values need to be written as field transforms. Use pod_to_document_with_transforms",
+        ));
+    }
+    Ok(lifted.document)
+}
+
+/// A document and the field values that have been lifted out of it, see [`pod_to_document_with_transforms`].
+#[derive(Debug, Default)]
+pub struct LiftedDocument {
+    /// The document without field values
+    pub document: dto::Document,
+    /// The field transforms, to be used for [`dto::Write::update_transforms`]
+    pub transforms: Vec<dto::FieldTransform>,
+    /// The escaped field paths of [`crate::documents::FieldValue::delete`] values.
+    /// Those need to be part of [`dto::Write::update_mask`].
+    pub deleted_fields: Vec<String>,
+}
+
+/// Converts a custom data type into a firebase google-rpc-api inspired heavily nested and wrapped type
+/// to be consumed by the Firebase REST API.
+///
+/// [`crate::documents::FieldValue`]s are lifted out of the document and returned as field transforms
+/// and deleted fields.
+///
+/// This is a low level API. You probably want to use [`crate::documents`] instead.
+pub fn pod_to_document_with_transforms<T>(pod: &T) -> Result<LiftedDocument>
+where
+    T: Serialize,
+{
+    let mut fields = match firestore_serde::to_value(pod)?.map_value {
+        Some(map_value) => map_value.fields.unwrap_or_default(),
+        None => return Err(FirebaseError::Generic("A document must serialize to a map")),
+    };
+    let mut lifted = LiftedDocument::default();
+    lift_field_values(&mut fields, &FieldPath::new(Vec::<String>::new()), &mut lifted)?;
+    lifted.document.fields = Some(fields);
+    Ok(lifted)
+}
+
+/// Removes all field values of the given map recursively and converts them into field transforms
+/// and deleted fields. The parent is the field path of the map.
+///
+/// Maps that only contained field values are removed as well. Otherwise they would be written
+/// as empty maps and, as part of an update mask, replace all sibling fields of the transformed fields.
+/// Maps that have been empty in the first place are kept.
+fn lift_field_values(
+    fields: &mut HashMap<String, dto::Value>,
+    parent: &FieldPath,
+    lifted: &mut LiftedDocument,
+) -> Result<()> {
+    let mut field_values = Vec::new();
+    for (key, value) in fields.iter_mut() {
+        let field_path = parent.child(key.as_str());
+        if let Some(field_value) = FieldValue::from_firebase_value(value)? {
+            match field_value.to_field_transform(field_path.to_string()) {
+                Some(transform) => lifted.transforms.push(transform),
+                None => lifted.deleted_fields.push(field_path.to_string()),
+            }
+            field_values.push(key.clone());
+        } else if let Some(inner) = value.map_value.as_mut().and_then(|map| map.fields.as_mut()) {
+            let was_empty = inner.is_empty();
+            lift_field_values(inner, &field_path, lifted)?;
+            if !was_empty && inner.is_empty() {
+                field_values.push(key.clone());
+            }
+        } else if contains_field_value(value) {
+            return Err(FirebaseError::Generic("Field values cannot be used within arrays"));
+        }
+    }
+    for key in field_values {
+        fields.remove(&key);
+    }
+    Ok(())
+}
+
+fn contains_field_value(value: &dto::Value) -> bool {
+    if let Some(fields) = value.map_value.as_ref().and_then(|map| map.fields.as_ref()) {
+        fields.contains_key(FIELD_VALUE_MARKER) || fields.values().any(contains_field_value)
+    } else if let Some(values) = value.array_value.as_ref().and_then(|array| array.values.as_ref()) {
+        values.iter().any(contains_field_value)
+    } else {
+        false
+    }
 }
 
 #[cfg(test)]
@@ -222,6 +304,141 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_pod_to_document_with_transforms() -> Result<()> {
+        use crate::documents::FieldValue;
+
+        #[derive(Serialize)]
+        struct Nested {
+            count: FieldValue,
+            name: String,
+        }
+        #[derive(Serialize)]
+        struct TransformPod {
+            updated: FieldValue,
+            #[serde(rename = "the stats")]
+            stats: Nested,
+        }
+
+        let t = TransformPod {
+            updated: FieldValue::server_timestamp(),
+            stats: Nested {
+                count: FieldValue::increment(2),
+                name: "abc".to_owned(),
+            },
+        };
+        assert!(pod_to_document(&t).is_err());
+
+        let LiftedDocument {
+            document, transforms, ..
+        } = pod_to_document_with_transforms(&t)?;
+        let fields = document.fields.unwrap();
+        assert!(fields.get("updated").is_none());
+        let stats = fields["the stats"].map_value.as_ref().unwrap().fields.as_ref().unwrap();
+        assert!(stats.get("count").is_none());
+        assert_eq!(stats["name"].string_value.as_deref(), Some("abc"));
+
+        assert_eq!(transforms.len(), 2);
+        let transform = |path: &str| {
+            transforms
+                .iter()
+                .find(|t| t.field_path.as_deref() == Some(path))
+                .expect("a transform for the field path")
+        };
+        assert_eq!(
+            transform("`the stats`.count")
+                .increment
+                .as_ref()
+                .unwrap()
+                .integer_value
+                .as_deref(),
+            Some("2")
+        );
+        assert_eq!(
+            transform("updated").set_to_server_value.as_deref(),
+            Some("REQUEST_TIME")
+        );
+
+        let in_array = vec![FieldValue::server_timestamp()];
+        assert!(pod_to_document_with_transforms(&serde_json::json!({ "a": in_array })).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_types_round_trip() -> Result<()> {
+        use crate::documents::{self, Bytes, DocumentReference, FirestoreTimestamp, GeoPoint};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct ValuesPod {
+            timestamp: FirestoreTimestamp,
+            #[serde(with = "documents::timestamp")]
+            datetime: chrono::DateTime<chrono::Utc>,
+            #[serde(with = "documents::timestamp::option")]
+            no_datetime: Option<chrono::DateTime<chrono::Utc>>,
+            location: GeoPoint,
+            reference: DocumentReference,
+            data: Bytes,
+            nothing: Option<String>,
+        }
+
+        let pod = ValuesPod {
+            timestamp: FirestoreTimestamp::now(),
+            datetime: "2020-04-28T14:52:51.250511Z".parse().unwrap(),
+            no_datetime: None,
+            location: GeoPoint::new(52.52, 13.4),
+            reference: DocumentReference::from_name("projects/p/databases/(default)/documents/users/abc")?,
+            data: Bytes(vec![0, 1, 254, 255]),
+            nothing: None,
+        };
+        let document = pod_to_document(&pod)?;
+        let fields = document.fields.as_ref().unwrap();
+        assert!(fields["timestamp"].timestamp_value.is_some());
+        assert_eq!(
+            fields["datetime"].timestamp_value.as_deref(),
+            Some("2020-04-28T14:52:51.250511Z")
+        );
+        assert_eq!(fields["no_datetime"].null_value.as_deref(), Some("NULL_VALUE"));
+        assert_eq!(
+            fields["location"].geo_point_value.as_ref().unwrap().latitude,
+            Some(52.52)
+        );
+        assert_eq!(
+            fields["reference"].reference_value.as_deref(),
+            Some("projects/p/databases/(default)/documents/users/abc")
+        );
+        assert_eq!(fields["data"].bytes_value.as_deref(), Some("AAH+/w=="));
+        assert_eq!(fields["nothing"].null_value.as_deref(), Some("NULL_VALUE"));
+        assert_eq!(pod.reference.path(), "users/abc");
+
+        // The document as returned by the REST API
+        let document: dto::Document = serde_json::from_value(serde_json::to_value(&document)?)?;
+        let read: ValuesPod = document_to_pod(&document, None)?;
+        assert_eq!(read, pod);
+        assert_eq!(
+            serde_json::to_value(pod_to_document(&read)?.fields)?,
+            serde_json::to_value(&document.fields)?
+        );
+
+        // Untyped json documents keep bytes, references and geo points, but timestamps become strings
+        let untyped: serde_json::Value = document_to_pod(&document, None)?;
+        let untyped_fields = pod_to_document(&untyped)?.fields.unwrap();
+        assert_eq!(untyped_fields["data"].bytes_value.as_deref(), Some("AAH+/w=="));
+        assert_eq!(
+            untyped_fields["datetime"].string_value.as_deref(),
+            Some("2020-04-28T14:52:51.250511Z")
+        );
+
+        // Dynamic documents keep timestamps as well
+        let dynamic: documents::DynamicDocument = document_to_pod(&document, None)?;
+        assert_eq!(
+            serde_json::to_value(pod_to_document(&dynamic)?.fields)?,
+            serde_json::to_value(&document.fields)?
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_pod_to_document() -> Result<()> {
         let t = DemoPod {
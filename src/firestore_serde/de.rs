@@ -0,0 +1,334 @@
+use super::Error;
+use crate::documents::{BYTES_MARKER, FIRESTORE_VALUE_TOKEN, GEO_POINT_MARKER, REFERENCE_MARKER, TIMESTAMP_MARKER};
+use crate::dto;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use serde::de::value::{MapDeserializer, StrDeserializer};
+use serde::de::{self, DeserializeSeed, Error as _, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use std::collections::{hash_map, HashMap};
+use std::iter::Enumerate;
+use std::slice;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A serde deserializer that converts Firestore values into rust values, see [`super::from_value`]
+pub struct Deserializer<'de> {
+    input: Input<'de>,
+}
+
+enum Input<'de> {
+    Value(&'de dto::Value),
+    /// The fields of a document
+    Fields(Option<&'de HashMap<String, dto::Value>>),
+}
+
+/// The kind of a Firestore value
+enum Kind<'de> {
+    Null,
+    Boolean(bool),
+    Integer(&'de str),
+    Double(f64),
+    Timestamp(&'de str),
+    String(&'de str),
+    Bytes(&'de str),
+    Reference(&'de str),
+    GeoPoint(&'de dto::LatLng),
+    Map(Option<&'de HashMap<String, dto::Value>>),
+    Array(&'de [dto::Value]),
+}
+
+impl<'de> Deserializer<'de> {
+    /// A deserializer for the given Firestore value
+    pub fn new(value: &'de dto::Value) -> Self {
+        Deserializer {
+            input: Input::Value(value),
+        }
+    }
+
+    /// A deserializer for the fields of a document, which are deserialized like a map value
+    pub fn from_fields(fields: Option<&'de HashMap<String, dto::Value>>) -> Self {
+        Deserializer {
+            input: Input::Fields(fields),
+        }
+    }
+
+    fn kind(&self) -> Kind<'de> {
+        let v = match self.input {
+            Input::Value(v) => v,
+            Input::Fields(fields) => return Kind::Map(fields),
+        };
+        if let Some(timestamp_value) = v.timestamp_value.as_deref() {
+            Kind::Timestamp(timestamp_value)
+        } else if let Some(integer_value) = v.integer_value.as_deref() {
+            Kind::Integer(integer_value)
+        } else if let Some(double_value) = v.double_value {
+            Kind::Double(double_value)
+        } else if let Some(map_value) = v.map_value.as_ref() {
+            Kind::Map(map_value.fields.as_ref())
+        } else if let Some(string_value) = v.string_value.as_deref() {
+            Kind::String(string_value)
+        } else if let Some(boolean_value) = v.boolean_value {
+            Kind::Boolean(boolean_value)
+        } else if let Some(array_value) = v.array_value.as_ref() {
+            Kind::Array(array_value.values.as_deref().unwrap_or_default())
+        } else if let Some(bytes_value) = v.bytes_value.as_deref() {
+            Kind::Bytes(bytes_value)
+        } else if let Some(reference_value) = v.reference_value.as_deref() {
+            Kind::Reference(reference_value)
+        } else if let Some(geo_point_value) = v.geo_point_value.as_ref() {
+            Kind::GeoPoint(geo_point_value)
+        } else {
+            Kind::Null
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.kind() {
+            Kind::Null => visitor.visit_unit(),
+            Kind::Boolean(v) => visitor.visit_bool(v),
+            Kind::Integer(v) => match v.parse::<i64>() {
+                Ok(v) => visitor.visit_i64(v),
+                Err(_) => Err(Error::custom(format!("invalid integer value '{}'", v))),
+            },
+            Kind::Double(v) => visitor.visit_f64(v),
+            // Timestamps are read as rfc3339 strings
+            Kind::Timestamp(v) | Kind::String(v) => visitor.visit_borrowed_str(v),
+            // Bytes, references and geo points are read in the serialized form of
+            // documents::Bytes, documents::DocumentReference and documents::GeoPoint
+            Kind::Bytes(v) => visitor.visit_map(marker(BYTES_MARKER, v)),
+            Kind::Reference(v) => visitor.visit_map(marker(REFERENCE_MARKER, v)),
+            Kind::GeoPoint(v) => {
+                let coordinates = [
+                    ("latitude", v.latitude.unwrap_or_default()),
+                    ("longitude", v.longitude.unwrap_or_default()),
+                ];
+                visitor.visit_map(marker(GEO_POINT_MARKER, MapDeserializer::new(coordinates.into_iter())))
+            }
+            Kind::Map(fields) => visitor.visit_map(MapAccess {
+                iter: fields.map(|fields| fields.iter()),
+                value: None,
+            }),
+            Kind::Array(values) => visitor.visit_seq(SeqAccess {
+                iter: values.iter().enumerate(),
+            }),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.kind() {
+            Kind::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.kind() {
+            Kind::Bytes(v) => visitor.visit_byte_buf(BASE64_STANDARD.decode(v).map_err(Error::custom)?),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value> {
+        match self.kind() {
+            // documents::FirestoreValue keeps timestamps, in the serialized form of documents::FirestoreTimestamp
+            Kind::Timestamp(v) if name == FIRESTORE_VALUE_TOKEN => visitor.visit_map(marker(TIMESTAMP_MARKER, v)),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.kind() {
+            Kind::String(variant) => visitor.visit_enum(StrDeserializer::<Error>::new(variant)),
+            Kind::Map(Some(fields)) if fields.len() == 1 => {
+                let (variant, value) = fields.iter().next().unwrap();
+                visitor.visit_enum(EnumAccess { variant, value })
+            }
+            _ => Err(Error::custom(
+                "expected a string or a map with a single key for an enum",
+            )),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+/// A map with a single entry, the serialized form of a value type like documents::Bytes
+fn marker<'de, V>(key: &'static str, value: V) -> MapDeserializer<'de, std::iter::Once<(&'static str, V)>, Error>
+where
+    V: IntoDeserializer<'de, Error>,
+{
+    MapDeserializer::new(std::iter::once((key, value)))
+}
+
+struct MapAccess<'de> {
+    iter: Option<hash_map::Iter<'de, String, dto::Value>>,
+    value: Option<(&'de str, &'de dto::Value)>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.iter.as_mut().and_then(Iterator::next) {
+            Some((key, value)) => {
+                self.value = Some((key, value));
+                seed.deserialize(MapKeyDeserializer { key })
+                    .map(Some)
+                    .map_err(|e| e.in_field(key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or_else(|| Error::custom("next_value_seed called before next_key_seed"))?;
+        seed.deserialize(Deserializer::new(value)).map_err(|e| e.in_field(key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.iter.as_ref().map(ExactSizeIterator::len).or(Some(0))
+    }
+}
+
+struct SeqAccess<'de> {
+    iter: Enumerate<slice::Iter<'de, dto::Value>>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        match self.iter.next() {
+            Some((index, value)) => seed
+                .deserialize(Deserializer::new(value))
+                .map(Some)
+                .map_err(|e| e.at_index(index)),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// An externally tagged enum variant, a map with the variant name as the only key
+struct EnumAccess<'de> {
+    variant: &'de str,
+    value: &'de dto::Value,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let variant = seed.deserialize(StrDeserializer::<Error>::new(self.variant))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(Deserializer::new(self.value)).map_err(|e: Error| e.in_field(self.variant))
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(Deserializer::new(self.value))
+            .map_err(|e| e.in_field(self.variant))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(Deserializer::new(self.value), visitor).map_err(|e| e.in_field(self.variant))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(Deserializer::new(self.value), visitor).map_err(|e| e.in_field(self.variant))
+    }
+}
+
+/// Deserializes map keys, which are stored as strings. Integer and boolean keys are parsed.
+struct MapKeyDeserializer<'de> {
+    key: &'de str,
+}
+
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                match self.key.parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => visitor.visit_borrowed_str(self.key),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for MapKeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_borrowed_str(self.key)
+    }
+
+    deserialize_parsed_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i64,
+        deserialize_i16 => visit_i64,
+        deserialize_i32 => visit_i64,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u64,
+        deserialize_u16 => visit_u64,
+        deserialize_u32 => visit_u64,
+        deserialize_u64 => visit_u64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_enum(StrDeserializer::<Error>::new(self.key))
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 f32 f64 char str string bytes byte_buf unit unit_struct
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
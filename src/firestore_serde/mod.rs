@@ -0,0 +1,323 @@
+//! # Serde conversion between rust types and Firestore values
+//! A serde [`Serializer`] and [`Deserializer`] that map rust types directly to and from [`dto::Value`]s,
+//! without a `serde_json::Value` in between.
+//!
+//! * Integers are stored as integer values. A `u64` above `i64::MAX` does not fit and is an error.
+//! * `f32` and `f64` are stored as double values, including NaN and the infinities.
+//! * Byte slices and buffers (for example with `serde_bytes`) are stored as bytes values.
+//! * Enums are externally tagged like with serde_json: Unit variants are stored as strings,
+//!   all other variants as a map with the variant name as the only key.
+//! * Newtype structs are stored as their content.
+//! * Map keys can be strings, chars, integers, booleans or unit variants. They are stored as strings.
+//! * [`crate::documents::FirestoreTimestamp`], [`crate::documents::GeoPoint`],
+//!   [`crate::documents::DocumentReference`] and [`crate::documents::Bytes`] are stored as the respective values.
+//!
+//! Timestamp values are read as rfc3339 strings. Errors contain the path of the affected field.
+//!
+//! This is a low level API. You probably want to use [`crate::documents`] instead.
+//!
+//! Example:
+//! ```
+//! use serde::{Serialize, Deserialize};
+//! use firestore_db_and_auth::firestore_serde;
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! enum Shape { Circle { radius: f64 }, Empty }
+//!
+//! let value = firestore_serde::to_value(&vec![Shape::Circle { radius: f64::INFINITY }, Shape::Empty])?;
+//! let shapes: Vec<Shape> = firestore_serde::from_value(&value)?;
+//! assert_eq!(shapes[1], Shape::Empty);
+//!
+//! let err = firestore_serde::to_value(&vec![(1, u64::MAX)]).unwrap_err();
+//! assert_eq!(err.path(), "[0][1]");
+//! # Ok::<(), firestore_serde::Error>(())
+//! ```
+
+use super::dto;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+mod de;
+mod ser;
+
+pub use de::Deserializer;
+pub use ser::Serializer;
+
+/// Converts the given rust value into a Firestore value
+pub fn to_value<T>(value: &T) -> Result<dto::Value, Error>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(Serializer)
+}
+
+/// Converts the given Firestore value into a rust value
+pub fn from_value<'de, T>(value: &'de dto::Value) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(Deserializer::new(value))
+}
+
+/// Converts the fields of the given document into a rust value
+pub fn from_document<'de, T>(document: &'de dto::Document) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(Deserializer::from_fields(document.fields.as_ref()))
+}
+
+/// A serialization or deserialization error with the path of the affected field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    message: String,
+    /// The path segments, innermost first
+    path: Vec<PathSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+impl Error {
+    /// The error message without the path
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The path of the affected field, like "address.lines[2]". Empty if the error is about the value itself.
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in self.path.iter().rev() {
+            match segment {
+                PathSegment::Field(name) => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(name);
+                }
+                PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+
+    pub(crate) fn in_field(mut self, name: &str) -> Self {
+        self.path.push(PathSegment::Field(name.to_owned()));
+        self
+    }
+
+    pub(crate) fn at_index(mut self, index: usize) -> Self {
+        self.path.push(PathSegment::Index(index));
+        self
+    }
+
+    fn new(message: impl Into<String>) -> Self {
+        Error {
+            message: message.into(),
+            path: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{} at '{}'", self.message, self.path())
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::new(msg.to_string())
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::new(msg.to_string())
+    }
+}
+
+/// Firestore integers are signed 64 bit integers. Values above `i64::MAX` do not fit.
+pub(crate) fn integer_from_u64(v: u64) -> Result<i64, Error> {
+    i64::try_from(v).map_err(|_| Error::new(format!("{} exceeds the range of a Firestore integer", v)))
+}
+
+fn map_value(fields: HashMap<String, dto::Value>) -> dto::Value {
+    dto::Value {
+        map_value: Some(dto::MapValue { fields: Some(fields) }),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Meters(f64);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Event {
+        Started,
+        Moved(Meters),
+        Paused(u32, u32),
+        Renamed { from: String, to: String },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Pod {
+        id: u64,
+        offset: i8,
+        ratio: f32,
+        distance: Meters,
+        events: Vec<Event>,
+        by_round: BTreeMap<u16, String>,
+        flags: HashMap<bool, char>,
+        raw: serde_json::Value,
+        nothing: Option<()>,
+        tuple: (String, i64),
+    }
+
+    fn pod() -> Pod {
+        Pod {
+            id: i64::MAX as u64,
+            offset: -3,
+            ratio: 0.5,
+            distance: Meters(f64::NAN),
+            events: vec![
+                Event::Started,
+                Event::Moved(Meters(12.5)),
+                Event::Paused(1, 2),
+                Event::Renamed {
+                    from: "a".to_owned(),
+                    to: "b".to_owned(),
+                },
+            ],
+            by_round: vec![(1, "one".to_owned()), (2, "two".to_owned())].into_iter().collect(),
+            flags: vec![(true, 'y')].into_iter().collect(),
+            raw: serde_json::json!({"a": [1, "b", null]}),
+            nothing: None,
+            tuple: ("x".to_owned(), -1),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = to_value(&pod()).unwrap();
+        let fields = value.map_value.as_ref().unwrap().fields.as_ref().unwrap();
+        assert_eq!(fields["id"].integer_value.as_deref(), Some("9223372036854775807"));
+        assert!(fields["distance"].double_value.unwrap().is_nan());
+        assert_eq!(
+            fields["events"].array_value.as_ref().unwrap().values.as_ref().unwrap()[0]
+                .string_value
+                .as_deref(),
+            Some("Started")
+        );
+        assert!(
+            fields["by_round"].map_value.as_ref().unwrap().fields.as_ref().unwrap()["2"]
+                .string_value
+                .is_some()
+        );
+        assert_eq!(fields["nothing"].null_value.as_deref(), Some("NULL_VALUE"));
+
+        // Through the wire format of the REST API
+        let value: dto::Value = serde_json::from_value(serde_json::to_value(&value).unwrap()).unwrap();
+        let read: Pod = from_value(&value).unwrap();
+        assert!(read.distance.0.is_nan());
+        assert_eq!(read.events, pod().events);
+        assert_eq!(
+            Pod {
+                distance: Meters(0.0),
+                ..read
+            },
+            Pod {
+                distance: Meters(0.0),
+                ..pod()
+            }
+        );
+    }
+
+    #[test]
+    fn bytes_and_infinity() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Data<'a> {
+            #[serde(with = "serde_bytes_like")]
+            data: Vec<u8>,
+            max: f64,
+            name: &'a str,
+        }
+
+        mod serde_bytes_like {
+            pub fn serialize<S: serde::Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(data)
+            }
+
+            pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+                struct Visitor;
+                impl<'de> serde::de::Visitor<'de> for Visitor {
+                    type Value = Vec<u8>;
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        f.write_str("bytes")
+                    }
+                    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+                        Ok(v)
+                    }
+                }
+                deserializer.deserialize_byte_buf(Visitor)
+            }
+        }
+
+        let data = Data {
+            data: vec![0, 1, 254, 255],
+            max: f64::NEG_INFINITY,
+            name: "abc",
+        };
+        let value = to_value(&data).unwrap();
+        let json = serde_json::to_value(&value).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"mapValue": {"fields": {
+                "data": {"bytesValue": "AAH+/w=="},
+                "max": {"doubleValue": "-Infinity"},
+                "name": {"stringValue": "abc"},
+            }}})
+        );
+        let value: dto::Value = serde_json::from_value(json).unwrap();
+        assert_eq!(from_value::<Data>(&value).unwrap(), data);
+    }
+
+    #[test]
+    fn errors_with_path() {
+        let err = to_value(&pod_with_big_id()).unwrap_err();
+        assert_eq!(err.path(), "id");
+        assert_eq!(
+            err.to_string(),
+            "18446744073709551615 exceeds the range of a Firestore integer at 'id'"
+        );
+
+        let mut value = to_value(&pod()).unwrap();
+        let fields = value.map_value.as_mut().unwrap().fields.as_mut().unwrap();
+        let events = fields.get_mut("events").unwrap().array_value.as_mut().unwrap();
+        events.values.as_mut().unwrap()[2] = to_value(&serde_json::json!({"Paused": [1, "two"]})).unwrap();
+        let err = from_value::<Pod>(&value).unwrap_err();
+        assert_eq!(err.path(), "events[2].Paused[1]");
+
+        let err = to_value(&vec![(vec![1], 2)].into_iter().collect::<BTreeMap<Vec<u8>, u8>>()).unwrap_err();
+        assert_eq!(err.message(), "A map key must be a string, char, integer or boolean");
+    }
+
+    fn pod_with_big_id() -> Pod {
+        Pod { id: u64::MAX, ..pod() }
+    }
+}
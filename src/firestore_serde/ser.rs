@@ -0,0 +1,498 @@
+use super::{map_value, Error};
+use crate::documents::marker_content_to_firebase_value;
+use crate::dto;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use serde::ser::{self, Error as _, Serialize};
+use std::collections::HashMap;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A serde serializer that converts rust values into Firestore values, see [`super::to_value`]
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = dto::Value;
+    type Error = Error;
+
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeVariant<SerializeArray>;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeVariant<SerializeMap>;
+
+    fn serialize_bool(self, v: bool) -> Result<dto::Value> {
+        Ok(dto::Value {
+            boolean_value: Some(v),
+            ..Default::default()
+        })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<dto::Value> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<dto::Value> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<dto::Value> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<dto::Value> {
+        Ok(dto::Value {
+            integer_value: Some(v.to_string()),
+            ..Default::default()
+        })
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<dto::Value> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<dto::Value> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<dto::Value> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<dto::Value> {
+        self.serialize_i64(super::integer_from_u64(v)?)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<dto::Value> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<dto::Value> {
+        Ok(dto::Value {
+            double_value: Some(v),
+            ..Default::default()
+        })
+    }
+
+    fn serialize_char(self, v: char) -> Result<dto::Value> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<dto::Value> {
+        Ok(dto::Value {
+            string_value: Some(v.to_owned()),
+            ..Default::default()
+        })
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<dto::Value> {
+        Ok(dto::Value {
+            bytes_value: Some(BASE64_STANDARD.encode(v)),
+            ..Default::default()
+        })
+    }
+
+    fn serialize_none(self) -> Result<dto::Value> {
+        Ok(dto::Value {
+            null_value: Some("NULL_VALUE".to_owned()),
+            ..Default::default()
+        })
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<dto::Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<dto::Value> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<dto::Value> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<dto::Value> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<dto::Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<dto::Value> {
+        let value = value.serialize(Serializer).map_err(|e| e.in_field(variant))?;
+        Ok(variant_value(variant, value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray> {
+        Ok(SerializeArray {
+            values: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeArray> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeArray>> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap> {
+        Ok(SerializeMap {
+            fields: HashMap::with_capacity(len.unwrap_or_default()),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeMap>> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct SerializeArray {
+    values: Vec<dto::Value>,
+}
+
+impl SerializeArray {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let index = self.values.len();
+        self.values
+            .push(value.serialize(Serializer).map_err(|e| e.at_index(index))?);
+        Ok(())
+    }
+
+    fn into_value(self) -> dto::Value {
+        dto::Value {
+            array_value: Some(dto::ArrayValue {
+                values: Some(self.values),
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = dto::Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<dto::Value> {
+        Ok(self.into_value())
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = dto::Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<dto::Value> {
+        Ok(self.into_value())
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = dto::Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<dto::Value> {
+        Ok(self.into_value())
+    }
+}
+
+#[doc(hidden)]
+pub struct SerializeMap {
+    fields: HashMap<String, dto::Value>,
+    next_key: Option<String>,
+}
+
+impl SerializeMap {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<()> {
+        let value = value.serialize(Serializer).map_err(|e| e.in_field(&key))?;
+        self.fields.insert(key, value);
+        Ok(())
+    }
+
+    fn into_value(self) -> dto::Value {
+        // The serialized forms of FirestoreTimestamp, GeoPoint, DocumentReference and Bytes
+        if self.fields.len() == 1 {
+            let (key, value) = self.fields.iter().next().unwrap();
+            if let Some(value) = marker_content_to_firebase_value(key, value) {
+                return value;
+            }
+        }
+        map_value(self.fields)
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = dto::Value;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.next_key = Some(key.serialize(MapKeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| Error::custom("serialize_value called before serialize_key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<dto::Value> {
+        Ok(self.into_value())
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = dto::Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()> {
+        self.insert(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<dto::Value> {
+        Ok(self.into_value())
+    }
+}
+
+/// A tuple or struct variant, stored as a map with the variant name as the only key
+#[doc(hidden)]
+pub struct SerializeVariant<T> {
+    variant: &'static str,
+    inner: T,
+}
+
+fn variant_value(variant: &str, value: dto::Value) -> dto::Value {
+    map_value(HashMap::from([(variant.to_owned(), value)]))
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeArray> {
+    type Ok = dto::Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let variant = self.variant;
+        self.inner.push(value).map_err(|e| e.in_field(variant))
+    }
+
+    fn end(self) -> Result<dto::Value> {
+        Ok(variant_value(self.variant, self.inner.into_value()))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeMap> {
+    type Ok = dto::Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()> {
+        let variant = self.variant;
+        self.inner
+            .insert(key.to_owned(), value)
+            .map_err(|e| e.in_field(variant))
+    }
+
+    fn end(self) -> Result<dto::Value> {
+        Ok(variant_value(self.variant, map_value(self.inner.fields)))
+    }
+}
+
+/// Serializes map keys, which are stored as strings
+struct MapKeySerializer;
+
+fn key_error() -> Error {
+    Error::custom("A map key must be a string, char, integer or boolean")
+}
+
+impl ser::Serializer for MapKeySerializer {
+    type Ok = String;
+    type Error = Error;
+
+    type SerializeSeq = ser::Impossible<String, Error>;
+    type SerializeTuple = ser::Impossible<String, Error>;
+    type SerializeTupleStruct = ser::Impossible<String, Error>;
+    type SerializeTupleVariant = ser::Impossible<String, Error>;
+    type SerializeMap = ser::Impossible<String, Error>;
+    type SerializeStruct = ser::Impossible<String, Error>;
+    type SerializeStructVariant = ser::Impossible<String, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<String> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String> {
+        Err(key_error())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String> {
+        Err(key_error())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String> {
+        Ok(v.to_owned())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String> {
+        Err(key_error())
+    }
+
+    fn serialize_none(self) -> Result<String> {
+        Err(key_error())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<String> {
+        Err(key_error())
+    }
+
+    fn serialize_unit(self) -> Result<String> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<String> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<String> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String> {
+        Err(key_error())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(key_error())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(key_error())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(key_error())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(key_error())
+    }
+}
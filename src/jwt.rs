@@ -10,6 +10,8 @@ use std::ops::Add;
 use std::slice::Iter;
 
 use crate::errors::FirebaseError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use biscuit::jwa::SignatureAlgorithm;
 use biscuit::{ClaimPresenceOptions, SingleOrMultiple, ValidationOptions};
 use cache_control::CacheControl;
@@ -97,6 +99,16 @@ pub(crate) async fn create_jwt_encoded<S: AsRef<str>>(
     Ok(jwt.encode(&secret.deref())?.encoded()?.encode())
 }
 
+/// Encodes the given jwt without a signature. The Firebase Auth emulator accepts such custom tokens.
+pub(crate) fn encode_unsigned(jwt: &AuthClaimsJWT) -> Result<String, Error> {
+    let header = serde_json::json!({"alg": "none", "typ": "JWT"});
+    Ok(format!(
+        "{}.{}.",
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(jwt.payload()?)?)
+    ))
+}
+
 /// Returns true if the access token (assumed to be a jwt) has expired
 ///
 /// An error is returned if the given access token string is not a jwt
@@ -200,6 +212,11 @@ pub(crate) async fn verify_access_token(
 ) -> Result<TokenValidationResult, Error> {
     let token = AuthClaimsJWT::new_encoded(&access_token);
 
+    // The auth emulator issues unsigned tokens
+    if credentials.auth_emulator() {
+        return token_validation_result(token.unverified_payload()?);
+    }
+
     let header = token.unverified_header()?;
     let kid = header
         .registered
@@ -232,14 +249,25 @@ pub(crate) async fn verify_access_token(
     let claims = token.payload()?;
     claims.registered.validate(o)?;
 
-    let audience = match claims.registered.audience.as_ref().unwrap() {
-        SingleOrMultiple::Single(v) => v.to_string(),
-        SingleOrMultiple::Multiple(v) => v.get(0).unwrap().to_string(),
+    token_validation_result(claims.clone())
+}
+
+fn token_validation_result(claims: biscuit::ClaimsSet<JwtOAuthPrivateClaims>) -> Result<TokenValidationResult, Error> {
+    let audience = match claims.registered.audience.as_ref() {
+        Some(SingleOrMultiple::Single(v)) => v.to_string(),
+        Some(SingleOrMultiple::Multiple(v)) if !v.is_empty() => v[0].to_string(),
+        _ => return Err(FirebaseError::Generic("No jwt audience")),
     };
+    let subject = claims
+        .registered
+        .subject
+        .as_ref()
+        .ok_or(FirebaseError::Generic("No jwt subject"))?
+        .to_string();
 
     Ok(TokenValidationResult {
-        claims: claims.private.clone(),
-        subject: claims.registered.subject.as_ref().unwrap().to_string(),
+        claims: claims.private,
+        subject,
         audience,
     })
 }
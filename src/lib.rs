@@ -4,8 +4,10 @@
 pub mod credentials;
 pub mod documents;
 pub mod dto;
+pub mod emulator;
 pub mod errors;
 pub mod firebase_rest_to_rust;
+pub mod firestore_serde;
 pub mod jwt;
 pub mod sessions;
 pub mod users;
@@ -19,6 +21,9 @@ pub use jwt::JWKSet;
 pub use sessions::service_account::Session as ServiceSession;
 pub use sessions::user::Session as UserSession;
 
+/// The id of the default Firestore database of a project
+pub const DEFAULT_DATABASE_ID: &str = "(default)";
+
 /// Authentication trait.
 ///
 /// This trait is implemented by [`crate::sessions`].
@@ -31,6 +36,12 @@ pub trait FirebaseAuthBearer {
     /// Return the project ID. This is required for the firebase REST API.
     fn project_id(&self) -> &str;
 
+    /// Return the Firestore database ID. Defaults to "(default)", the default database of the project.
+    /// Override this to access a named database.
+    fn database_id(&self) -> &str {
+        DEFAULT_DATABASE_ID
+    }
+
     /// An access token. If a refresh token is known and the access token expired,
     /// the implementation should try to refresh the access token before returning.
     async fn access_token(&self) -> String;
@@ -38,6 +49,12 @@ pub trait FirebaseAuthBearer {
     /// The access token, unchecked. Might be expired or in other ways invalid.
     async fn access_token_unchecked(&self) -> String;
 
+    /// The local Firestore and Firebase Auth emulators to talk to instead of the Google APIs, if any.
+    /// Defaults to None.
+    fn emulator(&self) -> Option<&emulator::Emulator> {
+        None
+    }
+
     /// The reqwest http client.
     /// The `Client` holds a connection pool internally, so it is advised that it is reused for multiple, successive connections.
     fn client(&self) -> &reqwest::Client;
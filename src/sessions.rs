@@ -4,12 +4,13 @@
 
 #![allow(unused_imports)]
 use super::credentials;
+use super::emulator::{auth_url, Emulator, EMULATOR_OWNER_TOKEN};
 use super::errors::{extract_google_api_error, extract_google_api_error_async, FirebaseError};
 use super::jwt::{
-    create_jwt, is_expired, jwt_update_expiry_if, verify_access_token, AuthClaimsJWT, JWT_AUDIENCE_FIRESTORE,
-    JWT_AUDIENCE_IDENTITY,
+    create_jwt, encode_unsigned, is_expired, jwt_update_expiry_if, verify_access_token, AuthClaimsJWT,
+    JWT_AUDIENCE_FIRESTORE, JWT_AUDIENCE_IDENTITY,
 };
-use super::FirebaseAuthBearer;
+use super::{FirebaseAuthBearer, DEFAULT_DATABASE_ID};
 
 use chrono::Duration;
 use serde::{Deserialize, Serialize};
@@ -25,16 +26,29 @@ pub mod user {
     use credentials::Credentials;
 
     #[inline]
-    fn token_endpoint(v: &str) -> String {
-        format!(
-            "https://www.googleapis.com/identitytoolkit/v3/relyingparty/verifyCustomToken?key={}",
-            v
-        )
+    fn token_endpoint(v: &str, emulator: Option<&Emulator>) -> String {
+        match emulator.and_then(|emulator| emulator.auth_host.as_ref()) {
+            // The emulator implements the v1 API only
+            Some(_) => auth_url(
+                emulator,
+                format!(
+                    "https://identitytoolkit.googleapis.com/v1/accounts:signInWithCustomToken?key={}",
+                    v
+                ),
+            ),
+            None => format!(
+                "https://www.googleapis.com/identitytoolkit/v3/relyingparty/verifyCustomToken?key={}",
+                v
+            ),
+        }
     }
 
     #[inline]
-    fn refresh_to_access_endpoint(v: &str) -> String {
-        format!("https://securetoken.googleapis.com/v1/token?key={}", v)
+    fn refresh_to_access_endpoint(v: &str, emulator: Option<&Emulator>) -> String {
+        auth_url(
+            emulator,
+            format!("https://securetoken.googleapis.com/v1/token?key={}", v),
+        )
     }
 
     /// Default OAuth2 Providers supported by Firebase.
@@ -82,6 +96,8 @@ pub mod user {
         access_token_: Arc<RwLock<String>>,
 
         project_id_: String,
+        database_id_: String,
+        emulator: Option<Emulator>,
         /// The http client for async operations. Replace or modify the client if you have special demands like proxy support
         pub client: reqwest::Client,
     }
@@ -92,6 +108,14 @@ pub mod user {
             &self.project_id_
         }
 
+        fn database_id(&self) -> &str {
+            &self.database_id_
+        }
+
+        fn emulator(&self) -> Option<&Emulator> {
+            self.emulator.as_ref()
+        }
+
         async fn access_token_unchecked(&self) -> String {
             self.access_token_.read().await.clone()
         }
@@ -107,7 +131,7 @@ pub mod user {
 
             if is_expired(&jwt, 0).unwrap() {
                 // Unwrap: the token is always valid at this point
-                if let Ok(response) = get_new_access_token(&self.api_key, &jwt).await {
+                if let Ok(response) = get_new_access_token(&self.api_key, &jwt, self.emulator.as_ref()).await {
                     *jwt = response.id_token.clone();
                     return response.id_token;
                 } else {
@@ -128,10 +152,11 @@ pub mod user {
     async fn get_new_access_token(
         api_key: &str,
         refresh_token: &str,
+        emulator: Option<&Emulator>,
     ) -> Result<RefreshTokenToAccessTokenResponse, FirebaseError> {
         let request_body = vec![("grant_type", "refresh_token"), ("refresh_token", refresh_token)];
 
-        let url = refresh_to_access_endpoint(api_key);
+        let url = refresh_to_access_endpoint(api_key, emulator);
         let client = reqwest::Client::new();
         let response = client.post(&url).form(&request_body).send().await?;
         Ok(response.json().await?)
@@ -236,12 +261,14 @@ pub mod user {
             refresh_token: &str,
         ) -> Result<Session, FirebaseError> {
             let r: RefreshTokenToAccessTokenResponse =
-                get_new_access_token(&credentials.api_key, refresh_token).await?;
+                get_new_access_token(&credentials.api_key, refresh_token, credentials.emulator.as_ref()).await?;
             Ok(Session {
                 user_id: r.user_id,
                 access_token_: Arc::new(RwLock::new(r.id_token)),
                 refresh_token: Some(r.refresh_token),
                 project_id_: credentials.project_id.to_owned(),
+                database_id_: DEFAULT_DATABASE_ID.to_owned(),
+                emulator: credentials.emulator.clone(),
                 api_key: credentials.api_key.clone(),
                 client: reqwest::Client::new(),
             })
@@ -270,15 +297,20 @@ pub mod user {
                 Some(user_id.to_owned()),
                 JWT_AUDIENCE_IDENTITY,
             )?;
-            let secret_lock = credentials.keys.read().await;
-            let secret = secret_lock
-                .secret
-                .as_ref()
-                .ok_or(FirebaseError::Generic("No private key added via add_keypair_key!"))?;
-            let encoded = jwt.encode(&secret.deref())?.encoded()?.encode();
+            let encoded = match credentials.auth_emulator() {
+                true => encode_unsigned(&jwt)?,
+                false => {
+                    let secret_lock = credentials.keys.read().await;
+                    let secret = secret_lock
+                        .secret
+                        .as_ref()
+                        .ok_or(FirebaseError::Generic("No private key added via add_keypair_key!"))?;
+                    jwt.encode(&secret.deref())?.encoded()?.encode()
+                }
+            };
 
             let resp = reqwest::Client::new()
-                .post(&token_endpoint(&credentials.api_key))
+                .post(&token_endpoint(&credentials.api_key, credentials.emulator.as_ref()))
                 .json(&CustomJwtToFirebaseID::new(encoded, with_refresh_token))
                 .send()
                 .await?;
@@ -290,6 +322,8 @@ pub mod user {
                 access_token_: Arc::new(RwLock::new(r.idToken)),
                 refresh_token: r.refreshToken,
                 project_id_: credentials.project_id.to_owned(),
+                database_id_: DEFAULT_DATABASE_ID.to_owned(),
+                emulator: credentials.emulator.clone(),
                 api_key: credentials.api_key.clone(),
                 client: reqwest::Client::new(),
             })
@@ -311,6 +345,8 @@ pub mod user {
             Ok(Session {
                 user_id: result.subject,
                 project_id_: result.audience,
+                database_id_: DEFAULT_DATABASE_ID.to_owned(),
+                emulator: credentials.emulator.clone(),
                 access_token_: Arc::new(RwLock::new(access_token.to_owned())),
                 refresh_token: None,
                 api_key: credentials.api_key.clone(),
@@ -318,6 +354,12 @@ pub mod user {
             })
         }
 
+        /// Use the named Firestore database with the given id instead of the default database of the project
+        pub fn with_database(mut self, database_id: impl Into<String>) -> Self {
+            self.database_id_ = database_id.into();
+            self
+        }
+
         /// Creates a new user session with OAuth2 provider token.
         /// If user don't exist it's create new user in firestore
         ///
@@ -337,8 +379,11 @@ pub mod user {
             request_uri: String,
             with_refresh_token: bool,
         ) -> Result<Session, FirebaseError> {
-            let uri = "https://identitytoolkit.googleapis.com/v1/accounts:signInWithIdp?key=".to_owned()
-                + &credentials.api_key;
+            let uri = auth_url(
+                credentials.emulator.as_ref(),
+                "https://identitytoolkit.googleapis.com/v1/accounts:signInWithIdp?key=".to_owned()
+                    + &credentials.api_key,
+            );
 
             let post_body = format!("access_token={}&providerId={}", access_token, get_provider(provider));
             let return_idp_credential = true;
@@ -426,24 +471,34 @@ pub mod session_cookie {
         id_token: String,
         duration: chrono::Duration,
     ) -> Result<String, FirebaseError> {
-        // Generate the assertion from the admin credentials
-        let assertion = crate::jwt::session_cookie::create_jwt_encoded(credentials, duration).await?;
-
-        // Request Google Oauth2 to retrieve the access token in order to create a session cookie
         let client = reqwest::blocking::Client::new();
-        let response_oauth2: Oauth2ResponseDTO = client
-            .post(GOOGLE_OAUTH2_URL)
-            .form(&[
-                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
-                ("assertion", &assertion),
-            ])
-            .send()?
-            .json()?;
+        let access_token = match credentials.auth_emulator() {
+            true => EMULATOR_OWNER_TOKEN.to_owned(),
+            false => {
+                // Generate the assertion from the admin credentials
+                let assertion = crate::jwt::session_cookie::create_jwt_encoded(credentials, duration).await?;
+
+                // Request Google Oauth2 to retrieve the access token in order to create a session cookie
+                let response_oauth2: Oauth2ResponseDTO = client
+                    .post(GOOGLE_OAUTH2_URL)
+                    .form(&[
+                        ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                        ("assertion", &assertion),
+                    ])
+                    .send()?
+                    .json()?;
+                response_oauth2.access_token
+            }
+        };
 
         // Create a session cookie with the access token previously retrieved
+        let url = auth_url(
+            credentials.emulator.as_ref(),
+            identitytoolkit_url(&credentials.project_id),
+        );
         let response_session_cookie_json: CreateSessionCookieResponseDTO = client
-            .post(&identitytoolkit_url(&credentials.project_id))
-            .bearer_auth(&response_oauth2.access_token)
+            .post(&url)
+            .bearer_auth(&access_token)
             .json(&SessionLoginDTO {
                 id_token,
                 valid_duration: duration.num_seconds() as u64,
@@ -476,6 +531,7 @@ pub mod service_account {
         pub client: reqwest::Client,
         jwt: Arc<RwLock<AuthClaimsJWT>>,
         access_token_: Arc<RwLock<String>>,
+        database_id_: String,
     }
 
     #[async_trait::async_trait]
@@ -484,9 +540,23 @@ pub mod service_account {
             &self.credentials.project_id
         }
 
+        fn database_id(&self) -> &str {
+            &self.database_id_
+        }
+
+        fn emulator(&self) -> Option<&Emulator> {
+            self.credentials.emulator.as_ref()
+        }
+
         /// Return the encoded jwt to be used as bearer token. If the jwt
         /// issue_at is older than 50 minutes, it will be updated to the current time.
+        ///
+        /// The emulator's `owner` token is returned if the Firestore emulator is used.
         async fn access_token(&self) -> String {
+            if self.credentials.firestore_emulator() {
+                return EMULATOR_OWNER_TOKEN.to_owned();
+            }
+
             // Keeping the JWT and the access token in write mode so this area is
             // a single-entrace critical section for refreshes sake
             let mut access_token = self.access_token_.write().await;
@@ -533,6 +603,9 @@ pub mod service_account {
         /// A custom jwt is created and signed with the service account private key. This jwt is used
         /// as bearer token.
         ///
+        /// If the Firestore emulator is used, no jwt is signed and the emulator's `owner` token is the bearer token.
+        /// See [`Credentials::with_emulator`].
+        ///
         /// See https://developers.google.com/identity/protocols/OAuth2ServiceAccount
         pub async fn new(credentials: Credentials) -> Result<Session, FirebaseError> {
             let scope: Option<Iter<String>> = None;
@@ -544,7 +617,9 @@ pub mod service_account {
                 None,
                 JWT_AUDIENCE_FIRESTORE,
             )?;
-            let encoded = {
+            let encoded = if credentials.firestore_emulator() {
+                EMULATOR_OWNER_TOKEN.to_owned()
+            } else {
                 let secret_lock = credentials.keys.read().await;
                 let secret = secret_lock
                     .secret
@@ -556,12 +631,19 @@ pub mod service_account {
             Ok(Session {
                 access_token_: Arc::new(RwLock::new(encoded)),
                 jwt: Arc::new(RwLock::new(jwt)),
+                database_id_: DEFAULT_DATABASE_ID.to_owned(),
 
                 credentials,
                 client: reqwest::Client::new(),
             })
         }
 
+        /// Use the named Firestore database with the given id instead of the default database of the project
+        pub fn with_database(mut self, database_id: impl Into<String>) -> Self {
+            self.database_id_ = database_id.into();
+            self
+        }
+
         pub async fn verify_token(&self, token: &str) -> Result<TokenValidationResult, FirebaseError> {
             self.credentials.verify_token(token).await
         }
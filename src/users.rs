@@ -4,6 +4,7 @@
 
 use super::errors::{extract_google_api_error_async, Result};
 
+use super::emulator::{auth_url, Emulator};
 use super::sessions::{service_account, user};
 use serde::{Deserialize, Serialize};
 
@@ -57,8 +58,11 @@ struct UserRequest {
 }
 
 #[inline]
-fn firebase_auth_url(v: &str, v2: &str) -> String {
-    format!("https://identitytoolkit.googleapis.com/v1/accounts:{}?key={}", v, v2)
+fn firebase_auth_url(v: &str, v2: &str, emulator: Option<&Emulator>) -> String {
+    auth_url(
+        emulator,
+        format!("https://identitytoolkit.googleapis.com/v1/accounts:{}?key={}", v, v2),
+    )
 }
 
 /// Retrieve information about the firebase auth user associated with the given user session
@@ -67,7 +71,7 @@ fn firebase_auth_url(v: &str, v2: &str) -> String {
 /// - INVALID_ID_TOKEN
 /// - USER_NOT_FOUND
 pub async fn user_info(session: &user::Session) -> Result<FirebaseAuthUserResponse> {
-    let url = firebase_auth_url("lookup", &session.api_key);
+    let url = firebase_auth_url("lookup", &session.api_key, session.emulator());
 
     let resp = session
         .client()
@@ -89,7 +93,7 @@ pub async fn user_info(session: &user::Session) -> Result<FirebaseAuthUserRespon
 /// - INVALID_ID_TOKEN
 /// - USER_NOT_FOUND
 pub async fn user_remove(session: &user::Session) -> Result<()> {
-    let url = firebase_auth_url("delete", &session.api_key);
+    let url = firebase_auth_url("delete", &session.api_key, session.emulator());
     let resp = session
         .client()
         .post(&url)
@@ -125,7 +129,7 @@ async fn sign_up_in(
     password: &str,
     action: &str,
 ) -> Result<user::Session> {
-    let url = firebase_auth_url(action, &session.credentials.api_key);
+    let url = firebase_auth_url(action, &session.credentials.api_key, session.emulator());
     let resp = session
         .client()
         .post(&url)